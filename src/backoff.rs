@@ -0,0 +1,87 @@
+use tokio::time::{Duration, Instant};
+
+/// Delays between reconnection attempts.
+///
+/// Instead of resetting once a connection succeeds, the failure count decays over time.
+/// That way a socket that keeps appearing and disappearing still backs off,
+/// while a connection that stayed up for a while is retried quickly again.
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    /// The delay after a single failure
+    base: Duration,
+    /// The longest delay between attempts
+    max: Duration,
+    /// After this long without failures, the failure count is halved
+    half_life: Duration,
+    failures: f64,
+    last_failure: Option<Instant>,
+}
+
+impl Backoff {
+    pub fn new(base: Duration, max: Duration, half_life: Duration) -> Self {
+        Self {
+            base,
+            max,
+            half_life,
+            failures: 0.0,
+            last_failure: None,
+        }
+    }
+
+    /// Records a failure and returns how long to wait before the next attempt.
+    pub fn failure(&mut self) -> Duration {
+        self.failure_at(Instant::now())
+    }
+
+    /// Like [`Backoff::failure`], for a failure at the given time.
+    pub fn failure_at(&mut self, now: Instant) -> Duration {
+        if let Some(last_failure) = self.last_failure {
+            let half_lives =
+                now.duration_since(last_failure).as_secs_f64() / self.half_life.as_secs_f64();
+            self.failures *= 0.5f64.powf(half_lives);
+        }
+        self.failures += 1.0;
+        self.last_failure = Some(now);
+
+        // Capping the exponent keeps the multiplication from overflowing, the delay is capped anyway
+        let factor = 2f64.powf((self.failures - 1.0).min(32.0));
+        self.base.mul_f64(factor).min(self.max)
+    }
+}
+
+/// Lets through at most one occurrence of something per interval, e.g. to keep repeated errors from flooding the logs.
+#[derive(Debug, Clone)]
+pub struct Throttle {
+    interval: Duration,
+    last: Option<Instant>,
+    /// Occurrences since the last one that was let through
+    suppressed: u32,
+}
+
+impl Throttle {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            last: None,
+            suppressed: 0,
+        }
+    }
+
+    /// Records an occurrence. If it should be let through, returns how many were suppressed since the last one.
+    pub fn hit(&mut self) -> Option<u32> {
+        self.hit_at(Instant::now())
+    }
+
+    pub fn hit_at(&mut self, now: Instant) -> Option<u32> {
+        match self.last {
+            Some(last) if now.duration_since(last) < self.interval => {
+                self.suppressed += 1;
+                None
+            }
+            _ => {
+                self.last = Some(now);
+                Some(std::mem::take(&mut self.suppressed))
+            }
+        }
+    }
+}
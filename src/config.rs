@@ -0,0 +1,851 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use serde::Serialize;
+
+use crate::{
+    error::ConfigError, event::EventType, message::MessageType, objects::WorkspaceInfoEnvelope,
+};
+
+/// Options of the daemon, parsed from the command line.
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// The file the options were read from, in addition to the command line
+    pub config_file: Option<PathBuf>,
+    /// The sway events to subscribe to
+    pub events: Vec<String>,
+    /// Events that are handled without updating eww
+    pub no_update: Vec<EventType>,
+    /// A file to write the logs to
+    pub log_file: Option<PathBuf>,
+    /// Once the log file is larger than this, it is rotated
+    pub log_file_max_bytes: Option<u64>,
+    /// Only log to the log file and not to stderr
+    pub log_file_only: bool,
+    /// A command to run when sway exits
+    pub shutdown_command: Option<Vec<String>>,
+    /// A command to run before connecting to sway
+    pub startup_command: Option<Vec<String>>,
+    /// Keep going if the startup command fails
+    pub ignore_startup_failure: bool,
+    /// Order the workspaces by name instead of putting them in numbered slots
+    pub prefer_name_over_num: bool,
+    /// Names for the placeholders of workspaces that don't exist, keyed by their number
+    pub ws_names: HashMap<isize, String>,
+    /// Include the app ids of each workspace's windows in `ws_info`
+    pub workspace_app_ids: bool,
+    /// How `ws_info` is serialized
+    pub ws_format: WorkspaceFormat,
+    /// Which workspaces are included in `ws_info`
+    pub ws_scope: WorkspaceScope,
+    /// Additionally publish the state of each workspace slot as separate variables like `ws_1_focused`
+    pub flat_ws_vars: bool,
+    /// Wrap `ws_info` in an object with the version of its format. Only applies to the JSON format.
+    pub ws_envelope: bool,
+    /// Publish what each seat has focused
+    pub seat_focus: bool,
+    /// Publish the input devices and the seats they are attached to
+    pub inputs: bool,
+    /// Publish the layout of the focused container
+    pub layout: bool,
+    /// Query the workspaces and the binding mode again after sway reloaded its config
+    pub refresh_on_reload: bool,
+    /// How long window titles need to settle before `active_window` is updated. 0 disables this.
+    pub title_debounce_ms: u64,
+    /// How `active_window` is serialized
+    pub active_window_format: ActiveWindowFormat,
+    /// How variables holding JSON are formatted
+    pub json_format: JsonFormat,
+    /// Icon names for `active_window_icon`, keyed by app id
+    pub icons: HashMap<String, String>,
+    /// The icon for windows whose app id isn't in `icons`
+    pub default_icon: String,
+    /// How many window titles are kept in `window_history`. 0 disables it.
+    pub history_size: usize,
+    /// A terminal to which the active window title is written as its title
+    pub terminal_title: Option<PathBuf>,
+    /// Write the variables into this JSON file instead of updating eww
+    pub output_file: Option<PathBuf>,
+    /// Where the variables are sent
+    pub output: OutputBackend,
+    /// The bus the DBus signals are sent on
+    pub dbus_bus: DbusBus,
+    /// The object path the DBus signals are sent from
+    pub dbus_path: String,
+    /// The interface of the DBus signals
+    pub dbus_interface: String,
+    /// Skip mode events that don't change the binding mode
+    pub coalesce_modes: bool,
+    /// Only set `binding_mode` on mode changes and never touch `binding_active`
+    pub no_default_mode_reset: bool,
+    /// Binding modes that don't update the mode variables, e.g. short-lived ones that would only flicker
+    pub mode_ignore: Vec<String>,
+    /// Outputs for which the mode variables are additionally set with the output name as a suffix
+    pub mode_outputs: Vec<String>,
+    /// The compositor we're talking to. Resolved from the socket environment variables if set to auto.
+    pub compositor: Compositor,
+    /// The IPC socket to connect to instead of the one of the running compositor
+    pub socket: Option<String>,
+    /// Whether `socket` is a Unix socket or a TCP address
+    pub socket_type: SocketType,
+    /// Reconnect when the connection to the compositor is lost, instead of exiting
+    pub reconnect: bool,
+    /// Publish the current state once and exit instead of subscribing to events
+    pub once: bool,
+    /// Send this request, print the reply and exit
+    pub query: Option<MessageType>,
+    /// Print the sockets we would connect to and exit
+    pub print_socket: bool,
+    /// Print the events we can subscribe to and exit
+    pub list_events: bool,
+    /// How often a failing reconnection is logged at most. Attempts in between are only logged at the debug level.
+    pub reconnect_log_interval_ms: u64,
+    /// Log the raw bytes of every frame read from the compositor
+    pub verbose_protocol: bool,
+    /// How long to wait for the reply to a request. 0 waits forever.
+    pub request_timeout_ms: u64,
+    /// How many events are buffered while we're busy. Further events are dropped.
+    pub event_buffer_size: NonZeroUsize,
+    /// A Unix socket on which we accept sway commands to run
+    pub control_socket: Option<PathBuf>,
+    /// The eww executable to use instead of looking it up in `PATH`
+    pub eww_binary: Option<PathBuf>,
+    /// The config directory of the eww daemon to update, if there are several
+    pub eww_config_dir: Option<PathBuf>,
+    /// Wait for the eww daemon to answer before setting any variables
+    pub wait_for_eww: bool,
+    /// How long to wait for the eww daemon at most
+    pub wait_for_eww_ms: u64,
+    /// Run `eww update` even if it wouldn't change the variable
+    pub no_dedup: bool,
+    /// Stop the daemon once an eww update fails, instead of only logging it
+    pub exit_on_eww_failure: bool,
+    /// How often a failed eww update is retried
+    pub eww_retries: u32,
+    /// How long to wait before retrying a failed eww update. This doubles with every retry.
+    pub eww_retry_ms: u64,
+    /// Set `sway_update_ready` once we subscribed to the events
+    pub emit_on_subscribe_success: bool,
+    /// How often `sway_update_alive` is updated. 0 disables this.
+    pub heartbeat_ms: u64,
+    /// How often the workspaces and the tree are queried in addition to the events. 0 disables this.
+    pub poll_interval_ms: u64,
+    /// After a failed eww update, set all variables again once eww is reachable
+    pub resync_on_eww_error: bool,
+    /// Values set on startup in addition to or instead of the defaults, see [`Config::initial_vars`]
+    pub initial_vars: Vec<(String, String)>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            config_file: None,
+            events: Vec::new(),
+            no_update: Vec::new(),
+            log_file: None,
+            log_file_max_bytes: None,
+            log_file_only: false,
+            shutdown_command: None,
+            startup_command: None,
+            ignore_startup_failure: false,
+            prefer_name_over_num: false,
+            ws_names: HashMap::new(),
+            workspace_app_ids: false,
+            ws_format: WorkspaceFormat::Json,
+            ws_scope: WorkspaceScope::All,
+            ws_envelope: false,
+            flat_ws_vars: false,
+            seat_focus: false,
+            inputs: false,
+            layout: false,
+            refresh_on_reload: false,
+            title_debounce_ms: 0,
+            active_window_format: ActiveWindowFormat::String,
+            json_format: JsonFormat::Compact,
+            history_size: 5,
+            icons: HashMap::new(),
+            default_icon: String::new(),
+            terminal_title: None,
+            output_file: None,
+            output: OutputBackend::Eww,
+            dbus_bus: DbusBus::Session,
+            dbus_path: "/org/swayupdate/Vars".to_owned(),
+            dbus_interface: "org.swayupdate.Vars".to_owned(),
+            coalesce_modes: false,
+            no_default_mode_reset: false,
+            mode_outputs: Vec::new(),
+            mode_ignore: Vec::new(),
+            compositor: Compositor::Auto,
+            socket: None,
+            socket_type: SocketType::Auto,
+            reconnect: false,
+            once: false,
+            query: None,
+            print_socket: false,
+            list_events: false,
+            reconnect_log_interval_ms: 30_000,
+            verbose_protocol: false,
+            request_timeout_ms: 5_000,
+            event_buffer_size: NonZeroUsize::new(256).unwrap(),
+            control_socket: None,
+            eww_binary: None,
+            eww_config_dir: None,
+            wait_for_eww: false,
+            wait_for_eww_ms: 10_000,
+            no_dedup: false,
+            exit_on_eww_failure: false,
+            eww_retries: 0,
+            eww_retry_ms: 100,
+            resync_on_eww_error: false,
+            heartbeat_ms: 0,
+            poll_interval_ms: 0,
+            emit_on_subscribe_success: false,
+            initial_vars: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WorkspaceFormat {
+    Json,
+    /// `<num>:<state>` for each workspace, separated by spaces
+    Kv,
+}
+
+impl FromStr for WorkspaceFormat {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(Self::Json),
+            "kv" => Ok(Self::Kv),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Which workspaces end up in `ws_info`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WorkspaceScope {
+    All,
+    /// Only the workspaces on the output of the focused workspace
+    FocusedOutput,
+    /// Only the workspaces on the output with this name
+    Output(String),
+}
+
+impl FromStr for WorkspaceScope {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "all" => Ok(Self::All),
+            "focused-output" => Ok(Self::FocusedOutput),
+            _ => match s.strip_prefix("output:") {
+                Some(output) if !output.is_empty() => Ok(Self::Output(output.to_owned())),
+                _ => Err(()),
+            },
+        }
+    }
+}
+
+/// What the variables are sent to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputBackend {
+    Eww,
+    /// A DBus signal for every variable that changes
+    #[cfg(feature = "dbus")]
+    Dbus,
+}
+
+impl FromStr for OutputBackend {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "eww" => Ok(Self::Eww),
+            #[cfg(feature = "dbus")]
+            "dbus" => Ok(Self::Dbus),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DbusBus {
+    Session,
+    System,
+}
+
+impl FromStr for DbusBus {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "session" => Ok(Self::Session),
+            "system" => Ok(Self::System),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActiveWindowFormat {
+    /// Only the window title
+    String,
+    /// An object with the title, app id, pid, urgency and focus of the window
+    Json,
+}
+
+impl FromStr for ActiveWindowFormat {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "string" => Ok(Self::String),
+            "json" => Ok(Self::Json),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JsonFormat {
+    Compact,
+    /// Indented over several lines, for debugging
+    Pretty,
+}
+
+impl JsonFormat {
+    /// Serializes a value for an eww variable.
+    pub fn to_string<T: Serialize + ?Sized>(self, value: &T) -> serde_json::Result<String> {
+        match self {
+            Self::Compact => serde_json::to_string(value),
+            Self::Pretty => serde_json::to_string_pretty(value),
+        }
+    }
+}
+
+impl FromStr for JsonFormat {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "compact" => Ok(Self::Compact),
+            "pretty" => Ok(Self::Pretty),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Compositor {
+    #[default]
+    Auto,
+    Sway,
+    I3,
+}
+
+impl FromStr for Compositor {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use Compositor::*;
+        Ok(match s {
+            "auto" => Auto,
+            "sway" => Sway,
+            "i3" => I3,
+            _ => return Err(()),
+        })
+    }
+}
+
+impl Display for Compositor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Compositor::Auto => "auto",
+            Compositor::Sway => "sway",
+            Compositor::I3 => "i3",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SocketType {
+    /// TCP for addresses like `host:port`, a Unix socket otherwise
+    #[default]
+    Auto,
+    Unix,
+    Tcp,
+}
+
+impl SocketType {
+    /// Resolves [`SocketType::Auto`] by the format of the address.
+    pub fn resolve(self, address: &str) -> Self {
+        if self != Self::Auto {
+            return self;
+        }
+        // Socket paths might contain colons too, but `host:port` never contains a slash
+        match address.rsplit_once(':') {
+            Some((host, port))
+                if !host.is_empty() && !address.contains('/') && port.parse::<u16>().is_ok() =>
+            {
+                Self::Tcp
+            }
+            _ => Self::Unix,
+        }
+    }
+}
+
+impl FromStr for SocketType {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(Self::Auto),
+            "unix" => Ok(Self::Unix),
+            "tcp" => Ok(Self::Tcp),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Config {
+    /// Parses the command line arguments (without the program name) after the options from the config file.
+    ///
+    /// The config file is either given with `--config` or looked for in the XDG config directory,
+    /// see [`find_config_file`]. Without one, this is the same as [`Config::from_args`].
+    pub fn load(args: impl IntoIterator<Item = String>) -> Result<Self, ConfigError> {
+        let args = args.into_iter().collect::<Vec<_>>();
+        let config = Self::from_args(args.clone())?;
+        let path = config.config_file.clone().or_else(|| {
+            let xdg_config_home = std::env::var_os("XDG_CONFIG_HOME").map(PathBuf::from);
+            let home = std::env::var_os("HOME").map(PathBuf::from);
+            find_config_file(xdg_config_home.as_deref(), home.as_deref())
+        });
+        let Some(path) = path else {
+            return Ok(config);
+        };
+
+        // Options given on the command line come last, so they override the file
+        let mut file_args = read_config_file(&path)?;
+        // Events from the command line replace the ones from the file instead of adding to them
+        if !config.events.is_empty() {
+            file_args.retain(|arg| arg.starts_with("--") && arg != "--subscribe-all");
+        }
+        let mut config = Self::from_args(file_args.into_iter().chain(args))?;
+        config.config_file = Some(path);
+        Ok(config)
+    }
+
+    /// Parses the command line arguments (without the program name).
+    ///
+    /// Every argument starting with `--` is an option, every other argument is an event to subscribe to.
+    /// Options take their value either inline (`--option=value`) or as the next argument.
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> Result<Self, ConfigError> {
+        let mut config = Self::default();
+        let mut args = args.into_iter();
+        let mut subscribe_all = false;
+
+        while let Some(arg) = args.next() {
+            let Some(option) = arg.strip_prefix("--") else {
+                config.events.push(arg);
+                continue;
+            };
+
+            let (option, inline_value) = match option.split_once('=') {
+                Some((option, value)) => (option, Some(value.to_owned())),
+                None => (option, None),
+            };
+            let mut value = || {
+                inline_value
+                    .clone()
+                    .or_else(|| args.next())
+                    .ok_or_else(|| ConfigError::MissingValue(option.to_owned()))
+            };
+
+            match option {
+                "config" => config.config_file = Some(PathBuf::from(value()?)),
+                "log-file" => config.log_file = Some(PathBuf::from(value()?)),
+                "log-file-max-bytes" => {
+                    config.log_file_max_bytes = Some(parse_value(option, &value()?)?)
+                }
+                "log-file-only" => config.log_file_only = true,
+                "shutdown-command" => {
+                    config.shutdown_command = Some(parse_command(option, &value()?)?)
+                }
+                "startup-command" => {
+                    config.startup_command = Some(parse_command(option, &value()?)?)
+                }
+                "ignore-startup-failure" => config.ignore_startup_failure = true,
+                "prefer-name-over-num" => config.prefer_name_over_num = true,
+                "compositor" => config.compositor = parse_value(option, &value()?)?,
+                "socket" => config.socket = Some(value()?),
+                "socket-type" => config.socket_type = parse_value(option, &value()?)?,
+                "reconnect" => config.reconnect = true,
+                "once" => config.once = true,
+                "query" => config.query = Some(parse_value(option, &value()?)?),
+                "print-socket" => config.print_socket = true,
+                "list-events" => config.list_events = true,
+                "subscribe-all" => subscribe_all = true,
+                "no-update" => {
+                    for event in value()?.split(',') {
+                        config.no_update.push(parse_value(option, event)?);
+                    }
+                }
+                "reconnect-log-interval-ms" => {
+                    config.reconnect_log_interval_ms = parse_value(option, &value()?)?
+                }
+                "verbose-protocol" => config.verbose_protocol = true,
+                "request-timeout-ms" => config.request_timeout_ms = parse_value(option, &value()?)?,
+                "event-buffer-size" => config.event_buffer_size = parse_value(option, &value()?)?,
+                "control-socket" => config.control_socket = Some(PathBuf::from(value()?)),
+                "active-window-format" => {
+                    config.active_window_format = parse_value(option, &value()?)?
+                }
+                "format" => config.json_format = parse_value(option, &value()?)?,
+                "icons" => config.icons.extend(parse_icons(option, &value()?)?),
+                "icon-file" => config
+                    .icons
+                    .extend(load_icon_file(&PathBuf::from(value()?))?),
+                "default-icon" => config.default_icon = value()?,
+                "history-size" => config.history_size = parse_value(option, &value()?)?,
+                "terminal-title" => config.terminal_title = Some(PathBuf::from(value()?)),
+                "output-file" => config.output_file = Some(PathBuf::from(value()?)),
+                "output" => {
+                    let value = value()?;
+                    #[cfg(not(feature = "dbus"))]
+                    if value == "dbus" {
+                        return Err(ConfigError::DbusDisabled);
+                    }
+                    config.output = parse_value(option, &value)?;
+                }
+                "dbus-bus" => config.dbus_bus = parse_value(option, &value()?)?,
+                "dbus-path" => config.dbus_path = value()?,
+                "dbus-interface" => config.dbus_interface = value()?,
+                "coalesce-modes" => config.coalesce_modes = true,
+                "no-default-mode-reset" => config.no_default_mode_reset = true,
+                "mode-ignore" => {
+                    config.mode_ignore = value()?.split(',').map(str::to_owned).collect()
+                }
+                "mode-outputs" => {
+                    config.mode_outputs = value()?.split(',').map(str::to_owned).collect()
+                }
+                "no-dedup" => config.no_dedup = true,
+                "exit-on-eww-failure" => config.exit_on_eww_failure = true,
+                "eww-binary" => config.eww_binary = Some(PathBuf::from(value()?)),
+                "eww-config-dir" => config.eww_config_dir = Some(PathBuf::from(value()?)),
+                "wait-for-eww" => config.wait_for_eww = true,
+                "wait-for-eww-ms" => config.wait_for_eww_ms = parse_value(option, &value()?)?,
+                "eww-retries" => config.eww_retries = parse_value(option, &value()?)?,
+                "eww-retry-ms" => config.eww_retry_ms = parse_value(option, &value()?)?,
+                "resync-on-eww-error" => config.resync_on_eww_error = true,
+                "initial-var" => {
+                    let assignment = value()?;
+                    let (var, val) =
+                        assignment
+                            .split_once('=')
+                            .ok_or_else(|| ConfigError::InvalidValue {
+                                option: option.to_owned(),
+                                value: assignment.clone(),
+                            })?;
+                    config.initial_vars.push((var.to_owned(), val.to_owned()));
+                }
+                "emit-on-subscribe-success" => config.emit_on_subscribe_success = true,
+                "heartbeat-ms" => config.heartbeat_ms = parse_value(option, &value()?)?,
+                "poll-interval-ms" => config.poll_interval_ms = parse_value(option, &value()?)?,
+                "workspace-app-ids" => config.workspace_app_ids = true,
+                "ws-names" => config.ws_names = parse_ws_names(option, &value()?)?,
+                "ws-format" => config.ws_format = parse_value(option, &value()?)?,
+                "ws-scope" => config.ws_scope = parse_value(option, &value()?)?,
+                "ws-envelope" => config.ws_envelope = true,
+                "flat-ws-vars" => config.flat_ws_vars = true,
+                "seat-focus" => config.seat_focus = true,
+                "inputs" => config.inputs = true,
+                "layout" => config.layout = true,
+                "refresh-on-reload" => config.refresh_on_reload = true,
+                "title-debounce-ms" => config.title_debounce_ms = parse_value(option, &value()?)?,
+                _ => return Err(ConfigError::UnknownOption(option.to_owned())),
+            }
+        }
+
+        #[cfg(feature = "dbus")]
+        if config.output == OutputBackend::Dbus && config.output_file.is_some() {
+            return Err(ConfigError::OutputFileWithDbus);
+        }
+        // The bus drops the connection on a signal with an invalid path or interface
+        #[cfg(feature = "dbus")]
+        for (option, value, valid) in [
+            (
+                "dbus-path",
+                &config.dbus_path,
+                crate::dbus::is_valid_path as fn(&str) -> bool,
+            ),
+            (
+                "dbus-interface",
+                &config.dbus_interface,
+                crate::dbus::is_valid_interface,
+            ),
+        ] {
+            if !valid(value) {
+                return Err(ConfigError::InvalidValue {
+                    option: option.to_owned(),
+                    value: value.clone(),
+                });
+            }
+        }
+
+        if subscribe_all {
+            if !config.events.is_empty() {
+                return Err(ConfigError::SubscribeAllWithEvents);
+            }
+            config.events = EventType::ALL
+                .iter()
+                .map(|event| event.name().to_owned())
+                .collect();
+        }
+
+        Ok(config)
+    }
+
+    /// The icon for a window with the given app id.
+    pub fn icon(&self, app_id: Option<&str>) -> &str {
+        app_id
+            .and_then(|app_id| self.icons.get(app_id))
+            .unwrap_or(&self.default_icon)
+    }
+
+    /// The variables set before connecting, so the bar doesn't show stale values until the first events arrive.
+    /// The defaults can be overridden with `--initial-var`.
+    pub fn initial_vars(&self) -> BTreeMap<String, String> {
+        let ws_info = match self.ws_format {
+            WorkspaceFormat::Json if self.ws_envelope => self
+                .json_format
+                .to_string(&WorkspaceInfoEnvelope::new(&[]))
+                .unwrap(),
+            WorkspaceFormat::Json => "[]".to_owned(),
+            WorkspaceFormat::Kv => String::new(),
+        };
+        let mut vars = BTreeMap::from([
+            ("binding_active".to_owned(), "false".to_owned()),
+            ("active_window".to_owned(), String::new()),
+            ("ws_info".to_owned(), ws_info),
+        ]);
+        vars.extend(self.initial_vars.iter().cloned());
+        vars
+    }
+}
+
+/// Parses the value of an option into `T`.
+fn parse_value<T: FromStr>(option: &str, value: &str) -> Result<T, ConfigError> {
+    value.parse().map_err(|_| ConfigError::InvalidValue {
+        option: option.to_owned(),
+        value: value.to_owned(),
+    })
+}
+
+/// Parses a list of workspace names like `1=term,2=web`.
+fn parse_ws_names(option: &str, value: &str) -> Result<HashMap<isize, String>, ConfigError> {
+    value
+        .split(',')
+        .map(|entry| {
+            let (num, name) = entry
+                .split_once('=')
+                .ok_or_else(|| ConfigError::InvalidValue {
+                    option: option.to_owned(),
+                    value: entry.to_owned(),
+                })?;
+            Ok((parse_value(option, num.trim())?, name.trim().to_owned()))
+        })
+        .collect()
+}
+
+/// Parses a list of icons like `firefox=web,foot=terminal`.
+fn parse_icons(option: &str, value: &str) -> Result<HashMap<String, String>, ConfigError> {
+    value
+        .split(',')
+        .map(|entry| {
+            let (app_id, icon) =
+                entry
+                    .split_once('=')
+                    .ok_or_else(|| ConfigError::InvalidValue {
+                        option: option.to_owned(),
+                        value: entry.to_owned(),
+                    })?;
+            Ok((app_id.trim().to_owned(), icon.trim().to_owned()))
+        })
+        .collect()
+}
+
+/// Loads icons from a file with a flat TOML table of strings, one `app_id = "icon"` per line.
+fn load_icon_file(path: &Path) -> Result<HashMap<String, String>, ConfigError> {
+    read_toml_table(path)?
+        .into_iter()
+        .map(|(line, app_id, icon)| match icon {
+            TomlValue::String(icon) => Ok((app_id, icon)),
+            _ => Err(ConfigError::InvalidFile {
+                path: path.to_owned(),
+                line,
+            }),
+        })
+        .collect()
+}
+
+/// Options that take a comma-separated list, an array in the config file is joined into a single value for them.
+const LIST_OPTIONS: &[&str] = &["mode-ignore", "mode-outputs", "ws-names"];
+/// Options that take a command, an array in the config file is its arguments.
+const COMMAND_OPTIONS: &[&str] = &["shutdown-command", "startup-command"];
+
+/// Turns the config file into arguments, which come before the ones from the command line.
+///
+/// Each key is an option without the leading `--`: `true` enables a flag, `false` leaves it out,
+/// an array repeats the option for every item, or is joined for one of the [`LIST_OPTIONS`] or [`COMMAND_OPTIONS`].
+/// The events are given as `events = ["window", ...]`.
+fn read_config_file(path: &Path) -> Result<Vec<String>, ConfigError> {
+    let mut args = Vec::new();
+    for (_, key, value) in read_toml_table(path)? {
+        let values = match value {
+            TomlValue::Bool(true) => {
+                args.push(format!("--{key}"));
+                continue;
+            }
+            TomlValue::Bool(false) => continue,
+            TomlValue::String(value) | TomlValue::Bare(value) => vec![value],
+            TomlValue::Array(values) if LIST_OPTIONS.contains(&key.as_str()) => {
+                vec![values.join(",")]
+            }
+            // Quoted, so the arguments come out the same when the command is split again
+            TomlValue::Array(values) if COMMAND_OPTIONS.contains(&key.as_str()) => {
+                vec![shell_words::join(values)]
+            }
+            TomlValue::Array(values) => values,
+        };
+        if key == "events" {
+            args.extend(values);
+        } else {
+            args.extend(values.into_iter().map(|value| format!("--{key}={value}")));
+        }
+    }
+    Ok(args)
+}
+
+/// The config file in `$XDG_CONFIG_HOME`, or in `~/.config` if that isn't set, if it exists.
+pub fn find_config_file(xdg_config_home: Option<&Path>, home: Option<&Path>) -> Option<PathBuf> {
+    let base = match xdg_config_home.filter(|dir| !dir.as_os_str().is_empty()) {
+        Some(dir) => dir.to_owned(),
+        None => home?.join(".config"),
+    };
+    Some(base.join("sway_update").join("config.toml")).filter(|path| path.is_file())
+}
+
+/// A value in a TOML file, as far as we support them.
+enum TomlValue {
+    String(String),
+    Bool(bool),
+    /// Anything else without quotes, e.g. a number
+    Bare(String),
+    /// An array of strings on a single line
+    Array(Vec<String>),
+}
+
+/// The line up to the first `#` that isn't inside a string.
+fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '#' if !quoted => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+/// Splits the items of an array at the commas that aren't inside a string.
+fn split_items(items: &str) -> impl Iterator<Item = &str> {
+    let mut quoted = false;
+    items.split(move |c| {
+        if c == '"' {
+            quoted = !quoted;
+        }
+        c == ',' && !quoted
+    })
+}
+
+/// Reads a flat TOML table, returning every key and value with its line number.
+///
+/// Only a subset of TOML is supported: no sections, no multi-line or escaped strings.
+fn read_toml_table(path: &Path) -> Result<Vec<(usize, String, TomlValue)>, ConfigError> {
+    let content = std::fs::read_to_string(path).map_err(|source| ConfigError::ReadFile {
+        path: path.to_owned(),
+        source,
+    })?;
+    let unquote = |s: &str| {
+        let s = s.trim();
+        s.strip_prefix('"')
+            .and_then(|s| s.strip_suffix('"'))
+            .map(str::to_owned)
+    };
+
+    let mut table = Vec::new();
+    for (i, line) in content.lines().enumerate() {
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+        let invalid = || ConfigError::InvalidFile {
+            path: path.to_owned(),
+            line: i + 1,
+        };
+        let (key, value) = line.split_once('=').ok_or_else(invalid)?;
+        // Bare keys are allowed in TOML, but strings have to be quoted
+        let key = unquote(key).unwrap_or_else(|| key.trim().to_owned());
+        if key.is_empty() {
+            return Err(invalid());
+        }
+
+        let value = value.trim();
+        let value = if let Some(items) = value.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+            let items = items.trim().trim_end_matches(',');
+            if items.trim().is_empty() {
+                TomlValue::Array(Vec::new())
+            } else {
+                let items = split_items(items).map(unquote).collect::<Option<_>>();
+                TomlValue::Array(items.ok_or_else(invalid)?)
+            }
+        } else if let Some(value) = unquote(value) {
+            TomlValue::String(value)
+        } else if value.starts_with('"') || value.is_empty() {
+            return Err(invalid());
+        } else {
+            match value {
+                "true" => TomlValue::Bool(true),
+                "false" => TomlValue::Bool(false),
+                _ => TomlValue::Bare(value.to_owned()),
+            }
+        };
+        table.push((i + 1, key, value));
+    }
+    Ok(table)
+}
+
+/// Splits a command into its arguments, which can be quoted like in a shell.
+/// The command is run directly and not through a shell.
+fn parse_command(option: &str, value: &str) -> Result<Vec<String>, ConfigError> {
+    let argv = shell_words::split(value).map_err(|_| ConfigError::InvalidValue {
+        option: option.to_owned(),
+        value: value.to_owned(),
+    })?;
+    if argv.is_empty() {
+        return Err(ConfigError::InvalidValue {
+            option: option.to_owned(),
+            value: value.to_owned(),
+        });
+    }
+    Ok(argv)
+}
@@ -0,0 +1,91 @@
+use std::{
+    os::unix::fs::FileTypeExt,
+    path::{Path, PathBuf},
+};
+
+use tokio::{
+    io::{AsyncBufReadExt, BufReader},
+    net::{UnixListener, UnixStream},
+    sync::mpsc,
+    task::JoinHandle,
+};
+use tracing::{debug, warn};
+
+/// A Unix socket on which clients send sway commands, one per line.
+/// The commands are forwarded to the daemon, which runs them over its connection to sway.
+pub struct ControlSocket {
+    path: PathBuf,
+    commands: mpsc::Receiver<String>,
+    listener: JoinHandle<()>,
+}
+
+impl ControlSocket {
+    pub fn bind(path: &Path) -> std::io::Result<Self> {
+        // A socket file left over from a previous run would make binding fail,
+        // anything else at that path is most likely a typo and left alone
+        match std::fs::symlink_metadata(path) {
+            Ok(metadata) if metadata.file_type().is_socket() => std::fs::remove_file(path)?,
+            Ok(_) => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::AlreadyExists,
+                    format!("{} exists and is not a socket", path.display()),
+                ))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        let listener = UnixListener::bind(path)?;
+        let (sender, commands) = mpsc::channel(16);
+
+        let listener = tokio::spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((stream, _)) => {
+                        tokio::spawn(forward_commands(stream, sender.clone()));
+                    }
+                    Err(e) => warn!("Error accepting control connection: {e}"),
+                }
+            }
+        });
+
+        Ok(Self {
+            path: path.to_owned(),
+            commands,
+            listener,
+        })
+    }
+
+    /// Waits for the next command sent by a client.
+    pub async fn recv(&mut self) -> Option<String> {
+        self.commands.recv().await
+    }
+}
+
+impl Drop for ControlSocket {
+    fn drop(&mut self) {
+        self.listener.abort();
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Reads commands from a client until it disconnects.
+async fn forward_commands(stream: UnixStream, sender: mpsc::Sender<String>) {
+    let mut lines = BufReader::new(stream).lines();
+    loop {
+        match lines.next_line().await {
+            Ok(Some(command)) if command.trim().is_empty() => continue,
+            Ok(Some(command)) => {
+                debug!("Received command \"{command}\" on control socket");
+                if sender.send(command).await.is_err() {
+                    // The daemon is gone
+                    return;
+                }
+            }
+            Ok(None) => return,
+            Err(e) => {
+                warn!("Error reading from control connection: {e}");
+                return;
+            }
+        }
+    }
+}
@@ -0,0 +1,1368 @@
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    convert::Infallible,
+    error::Error,
+    ops::RangeInclusive,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use tokio::{
+    io::{AsyncRead, AsyncWrite, AsyncWriteExt},
+    net::{TcpStream, UnixStream},
+    signal::unix::{signal, Signal, SignalKind},
+    time::{Duration, Instant, MissedTickBehavior},
+};
+use tracing::{debug, info, trace, warn};
+
+use crate::{
+    config::{ActiveWindowFormat, Compositor, Config, SocketType, WorkspaceFormat, WorkspaceScope},
+    control::ControlSocket,
+    error::{DaemonError, EventError, EventLoopError, EwwError, RequestError},
+    event::{
+        BarConfigUpdateEvent, EventType, ModeEvent, ShutdownEvent, WindowEvent, WorkspaceEvent,
+        WorkspaceEventChange,
+    },
+    message::{Message, MessageType},
+    objects::{
+        self, ActiveWindow, BindingState, CommandResult, Input, Node, Output, Seat, SeatFocus,
+        SeatInputs, Urgency, Version, Window, Workspace, WorkspaceInfo, WorkspaceInfoEnvelope,
+    },
+    publisher::Publisher,
+    reader::FrameReader,
+    run_command,
+};
+
+/// How long to wait for more workspace events before refreshing the workspaces
+const WORKSPACE_REFRESH_DELAY: Duration = Duration::from_millis(50);
+/// How long `new_window_ws` is set after a window opened, so the bar can flash that workspace
+const NEW_WINDOW_WS_DURATION: Duration = Duration::from_secs(1);
+/// The workspace numbers that always have a slot in `ws_info`, even if they don't exist
+const WORKSPACE_SLOTS: RangeInclusive<isize> = 1..=8;
+/// How long a tree is reused to look up the layout before requesting it again
+const TREE_CACHE_DURATION: Duration = Duration::from_millis(250);
+
+/// A connection to the compositor, whose state is published to eww.
+///
+/// The eww client outlives the connection, so what it knows about the variables carries over to the next one.
+pub struct Daemon<'a> {
+    /// Where requests are sent to the compositor
+    sway_socket: Box<dyn AsyncWrite + Unpin + Send>,
+    /// Where replies and events are read from
+    frames: FrameReader,
+    publisher: &'a mut Publisher,
+    /// Where clients send us commands to run
+    control: Option<ControlSocket>,
+    config: Config,
+    /// Numbers of the currently urgent workspaces
+    urgent_workspaces: Vec<isize>,
+    /// Titles of the currently urgent windows, keyed by their id
+    urgent_windows: BTreeMap<usize, String>,
+    /// The window whose title is in `active_window`
+    active_window_id: Option<usize>,
+    /// The active window variables waiting to be pushed once the title settled, and when it settles
+    pending_active_window: Option<(Vec<(&'static str, String)>, Instant)>,
+    /// The last binding mode we received
+    last_mode: Option<String>,
+    /// The app ids of the windows on each workspace, keyed by the workspace name
+    workspace_app_ids: HashMap<String, Vec<String>>,
+    /// The last workspace variables that reached eww
+    last_ws_vars: Option<Vec<(String, String)>>,
+    /// The output showing the focused workspace, according to the last workspaces we received
+    focused_workspace_output: Option<String>,
+    /// The last tree we received
+    tree: Option<Node>,
+    /// When we received `tree`
+    tree_received: Option<Instant>,
+    /// When the workspaces are refreshed after a burst of workspace events
+    workspaces_due: Option<Instant>,
+    /// When `new_window_ws` is cleared again
+    new_window_ws_due: Option<Instant>,
+    /// The container ids and titles of the last focused windows, the most recent first
+    window_history: VecDeque<(usize, String)>,
+    /// How many replies to requests that timed out are still to come
+    late_replies: usize,
+}
+
+impl<'a> Daemon<'a> {
+    /// Connects to the compositor at `socket_path` and binds the control socket, if one is configured.
+    ///
+    /// The socket is either the path of a Unix socket or a TCP `host:port`, see [`SocketType`].
+    #[tracing::instrument(skip(config))]
+    pub async fn new(
+        socket_path: &str,
+        publisher: &'a mut Publisher,
+        config: Config,
+    ) -> Result<Self, DaemonError> {
+        let connection = connect_socket(socket_path, config.socket_type).await?;
+        Self::with_connection(connection, publisher, config)
+    }
+
+    /// Like [`Daemon::new`], but over a connection that is already established.
+    pub(crate) fn with_connection(
+        (read_half, sway_socket): Connection,
+        publisher: &'a mut Publisher,
+        config: Config,
+    ) -> Result<Self, DaemonError> {
+        let control = config
+            .control_socket
+            .as_deref()
+            .map(ControlSocket::bind)
+            .transpose()
+            .map_err(DaemonError::ControlSocket)?;
+
+        let frames = FrameReader::spawn(read_half, config.event_buffer_size.get());
+
+        Ok(Self {
+            sway_socket,
+            frames,
+            publisher,
+            control,
+            config,
+            urgent_workspaces: Vec::new(),
+            urgent_windows: BTreeMap::new(),
+            active_window_id: None,
+            pending_active_window: None,
+            last_mode: None,
+            workspace_app_ids: HashMap::new(),
+            last_ws_vars: None,
+            focused_workspace_output: None,
+            tree: None,
+            tree_received: None,
+            workspaces_due: None,
+            new_window_ws_due: None,
+            window_history: VecDeque::new(),
+            late_replies: 0,
+        })
+    }
+
+    /// Waits for the reply to a request of the given type, for at most the configured timeout.
+    async fn read_response(&mut self, request_type: MessageType) -> Result<Message, RequestError> {
+        let read = async {
+            // Replies to requests that timed out might still arrive and are not ours
+            while self.late_replies > 0 {
+                self.frames.reply().await?;
+                self.late_replies -= 1;
+            }
+            Ok(self.frames.reply().await?)
+        };
+        if self.config.request_timeout_ms == 0 {
+            return read.await;
+        }
+
+        let timeout = Duration::from_millis(self.config.request_timeout_ms);
+        match tokio::time::timeout(timeout, read).await {
+            Ok(reply) => reply,
+            Err(_) => {
+                self.late_replies += 1;
+                Err(RequestError::Timeout(request_type, timeout))
+            }
+        }
+    }
+
+    /// Sends a request to the compositor and handles its reply.
+    pub async fn request(
+        &mut self,
+        request_type: MessageType,
+        payload: Option<impl AsRef<str>>,
+    ) -> Result<(), RequestError> {
+        let payload = payload.map_or(String::new(), |s| s.as_ref().to_owned());
+        let msg = request_type.frame(&payload);
+
+        // Send the message to the socket
+        self.sway_socket.write_all(&msg).await?;
+
+        let msg = match self.read_response(request_type).await {
+            Ok(msg) => msg,
+            Err(e) => {
+                warn!("Error while reading response. It will not be handled: {e}");
+                return Err(e);
+            }
+        };
+
+        info!("Received response of type {:?}", msg.message_type);
+        trace!("Event Payload: {}", &msg.payload);
+
+        self.handle_response(msg.message_type, &msg.payload).await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip_all, fields(payload_type))]
+    async fn handle_response(
+        &mut self,
+        payload_type: MessageType,
+        payload: impl AsRef<str>,
+    ) -> Result<(), RequestError> {
+        let payload = payload.as_ref();
+
+        trace!(payload = %AsRef::<str>::as_ref(payload), "handling response");
+        match payload_type {
+            MessageType::GetWorkspaces => {
+                let workspaces = {
+                    let workspaces: Vec<Workspace> =
+                        serde_json::from_str(payload).map_err(RequestError::Deserialize)?;
+                    // This is taken before filtering, since the focused workspace might be on an output out of scope.
+                    // While no workspace is focused, we stick to the output that was focused last.
+                    if let Some(workspace) = workspaces.iter().find(|workspace| workspace.focused) {
+                        self.focused_workspace_output = Some(workspace.output.clone());
+                    }
+                    let scope_output = match &self.config.ws_scope {
+                        WorkspaceScope::All => None,
+                        // Until we know the focused output, all workspaces are shown
+                        WorkspaceScope::FocusedOutput => self.focused_workspace_output.clone(),
+                        WorkspaceScope::Output(output) => Some(output.clone()),
+                    };
+                    workspaces
+                        .into_iter()
+                        .filter(|workspace| {
+                            scope_output
+                                .as_ref()
+                                .is_none_or(|output| &workspace.output == output)
+                        })
+                        .map(WorkspaceInfo::from)
+                        .collect::<Vec<_>>()
+                };
+
+                debug!(?workspaces);
+
+                // Only the existing workspaces, before the empty slots are filled in
+                let ws_list = WorkspaceInfo::list(&workspaces);
+
+                let workspace_infos = if self.config.prefer_name_over_num {
+                    // Named workspaces have no fixed slots, so only the existing ones are emitted
+                    let mut workspaces = workspaces;
+                    workspaces.sort_by(|a, b| a.name.cmp(&b.name));
+                    workspaces
+                } else {
+                    let workspaces = workspaces
+                        .into_iter()
+                        .map(|workspace| (workspace.num, workspace))
+                        .collect::<HashMap<_, _>>();
+
+                    // The remaining workspaces are filled in with default-constructed ones
+                    WORKSPACE_SLOTS
+                        .map(|i| {
+                            workspaces.get(&i).cloned().unwrap_or_else(|| {
+                                let name = self.config.ws_names.get(&i).cloned();
+                                WorkspaceInfo::new(&name.unwrap_or_else(|| i.to_string()), i)
+                            })
+                        })
+                        .collect::<Vec<_>>()
+                };
+
+                let mut workspace_infos = workspace_infos;
+                if self.config.workspace_app_ids {
+                    for workspace in &mut workspace_infos {
+                        let app_ids = self.workspace_app_ids.get(&workspace.name).cloned();
+                        workspace.app_ids = Some(app_ids.unwrap_or_default());
+                    }
+                }
+
+                let workspace_info_json = match self.config.ws_format {
+                    WorkspaceFormat::Json if self.config.ws_envelope => self
+                        .config
+                        .json_format
+                        .to_string(&WorkspaceInfoEnvelope::new(&workspace_infos))
+                        .map_err(RequestError::Serialize)?,
+                    WorkspaceFormat::Json => self
+                        .config
+                        .json_format
+                        .to_string(&workspace_infos)
+                        .map_err(RequestError::Serialize)?,
+                    // Each workspace as "<num>:<state>", separated by spaces
+                    WorkspaceFormat::Kv => workspace_infos
+                        .iter()
+                        .map(|workspace| {
+                            if self.config.prefer_name_over_num {
+                                format!("{}:{}", workspace.name, workspace.state())
+                            } else {
+                                format!("{}:{}", workspace.num, workspace.state())
+                            }
+                        })
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                };
+                let any_urgent = workspace_infos.iter().any(|workspace| workspace.urgent);
+                // While the focus moves between outputs, no workspace might be focused
+                let focused = workspace_infos.iter().find(|workspace| workspace.focused);
+                let focused_workspace =
+                    focused.map_or(String::new(), |workspace| workspace.num.to_string());
+                // The placeholders for empty slots aren't active
+                let workspace_count = workspace_infos
+                    .iter()
+                    .filter(|workspace| workspace.active)
+                    .count();
+
+                let mut vars = vec![
+                    ("ws_info".to_owned(), workspace_info_json.clone()),
+                    ("ws_list".to_owned(), ws_list),
+                    ("any_urgent".to_owned(), any_urgent.to_string()),
+                    ("focused_workspace".to_owned(), focused_workspace),
+                    ("workspace_count".to_owned(), workspace_count.to_string()),
+                ];
+                if self.config.flat_ws_vars {
+                    // Every slot gets its variables, so the bar never reads one that was never set
+                    for num in WORKSPACE_SLOTS {
+                        let workspace = workspace_infos
+                            .iter()
+                            .find(|workspace| workspace.num == num);
+                        let attributes = [
+                            (
+                                "active",
+                                workspace.is_some_and(|workspace| workspace.active),
+                            ),
+                            (
+                                "focused",
+                                workspace.is_some_and(|workspace| workspace.focused),
+                            ),
+                            (
+                                "urgent",
+                                workspace.is_some_and(|workspace| workspace.urgent),
+                            ),
+                            (
+                                "visible",
+                                workspace.is_some_and(|workspace| workspace.visible),
+                            ),
+                        ];
+                        vars.extend(attributes.into_iter().map(|(attribute, value)| {
+                            (format!("ws_{num}_{attribute}"), value.to_string())
+                        }));
+                    }
+                }
+                // ws_info alone isn't enough, e.g. `--ws-format kv` shows a focused workspace the same whether it's urgent or not
+                if !self.config.no_dedup && self.last_ws_vars.as_ref() == Some(&vars) {
+                    trace!("workspaces unchanged, skipping update");
+                } else {
+                    let success = self
+                        .publisher
+                        .set_vars(vars.clone())
+                        .await
+                        .map_err(|e| e.boxed())?;
+                    // A failed or muted update has to be sent again next time
+                    if success && !self.publisher.is_muted() {
+                        self.last_ws_vars = Some(vars);
+                    }
+                }
+
+                self.urgent_workspaces = workspace_infos
+                    .iter()
+                    .filter(|workspace| workspace.urgent)
+                    .map(|workspace| workspace.num)
+                    .collect();
+                self.update_urgency().await?;
+            }
+            MessageType::GetOutputs => {
+                let outputs: Vec<Output> =
+                    serde_json::from_str(payload).map_err(RequestError::Deserialize)?;
+
+                debug!(?outputs);
+
+                let outputs_json = self
+                    .config
+                    .json_format
+                    .to_string(&outputs)
+                    .map_err(RequestError::Serialize)?;
+                self.publisher
+                    .set_var("outputs", &outputs_json)
+                    .await
+                    .map_err(|e| e.boxed())?;
+
+                // The workspaces are requested before the outputs, so this is the current focused workspace
+                let focus_on_primary = self
+                    .focused_workspace_output
+                    .as_ref()
+                    .and_then(|name| outputs.iter().find(|output| &output.name == name))
+                    .is_some_and(|output| output.primary);
+                self.publisher
+                    .set_var("focus_on_primary", &focus_on_primary)
+                    .await
+                    .map_err(|e| e.boxed())?;
+
+                // The focused output is the one containing the focused workspace
+                let focused = match self.config.compositor {
+                    // i3 doesn't mark it, so we look for the output of the focused workspace instead
+                    Compositor::I3 => self
+                        .focused_workspace_output
+                        .as_ref()
+                        .and_then(|name| outputs.iter().find(|output| &output.name == name)),
+                    Compositor::Sway | Compositor::Auto => {
+                        outputs.iter().find(|output| output.focused)
+                    }
+                };
+                if let Some(output) = focused {
+                    let scale = output.scale.unwrap_or(1.0);
+                    // Sway reports the refresh rate in mHz
+                    let refresh = output
+                        .current_mode
+                        .map_or(0.0, |mode| mode.refresh as f64 / 1000.0);
+
+                    self.publisher
+                        .set_vars([
+                            ("focused_output", output.name.clone()),
+                            ("focused_output_scale", scale.to_string()),
+                            ("focused_output_refresh", refresh.to_string()),
+                        ])
+                        .await
+                        .map_err(|e| e.boxed())?;
+                }
+            }
+            MessageType::GetTree => {
+                let tree: Node =
+                    serde_json::from_str(payload).map_err(RequestError::Deserialize)?;
+
+                if self.config.workspace_app_ids {
+                    self.workspace_app_ids = tree
+                        .workspaces()
+                        .into_iter()
+                        .filter_map(|workspace| {
+                            let app_ids = workspace
+                                .windows()
+                                .into_iter()
+                                .filter_map(|window| window.app_id().map(str::to_owned))
+                                .collect();
+                            Some((workspace.name.clone()?, app_ids))
+                        })
+                        .collect();
+                }
+
+                self.publisher
+                    .set_vars([
+                        (
+                            "visible_window_count",
+                            tree.visible_window_count().to_string(),
+                        ),
+                        ("scratchpad_count", tree.scratchpad_count().to_string()),
+                    ])
+                    .await
+                    .map_err(|e| e.boxed())?;
+
+                self.tree = Some(tree);
+                self.tree_received = Some(Instant::now());
+            }
+            MessageType::GetMarks => {
+                let marks: Vec<String> =
+                    serde_json::from_str(payload).map_err(RequestError::Deserialize)?;
+                let marks_json = self
+                    .config
+                    .json_format
+                    .to_string(&marks)
+                    .map_err(RequestError::Serialize)?;
+                self.publisher
+                    .set_var("all_marks", &marks_json)
+                    .await
+                    .map_err(|e| e.boxed())?;
+            }
+            MessageType::GetSeats => {
+                let seats: Vec<Seat> =
+                    serde_json::from_str(payload).map_err(RequestError::Deserialize)?;
+
+                // Both options request the seats, but each only wants its own variable
+                if self.config.seat_focus {
+                    // The seats only report the id of the focused node, so we look up its output in the tree
+                    let seat_focus = seats
+                        .iter()
+                        .map(|seat| SeatFocus {
+                            output: self
+                                .tree
+                                .as_ref()
+                                .and_then(|tree| tree.output_of(seat.focus))
+                                .map(str::to_owned),
+                            name: seat.name.clone(),
+                            focus: seat.focus,
+                        })
+                        .collect::<Vec<_>>();
+
+                    let seat_focus_json = self
+                        .config
+                        .json_format
+                        .to_string(&seat_focus)
+                        .map_err(RequestError::Serialize)?;
+                    self.publisher
+                        .set_var("seat_focus", &seat_focus_json)
+                        .await
+                        .map_err(|e| e.boxed())?;
+                }
+                if self.config.inputs {
+                    let seats = seats.into_iter().map(SeatInputs::from).collect::<Vec<_>>();
+                    let seats_json = self
+                        .config
+                        .json_format
+                        .to_string(&seats)
+                        .map_err(RequestError::Serialize)?;
+                    self.publisher
+                        .set_var("seats", &seats_json)
+                        .await
+                        .map_err(|e| e.boxed())?;
+                }
+            }
+            MessageType::GetInputs => {
+                let inputs: Vec<Input> =
+                    serde_json::from_str(payload).map_err(RequestError::Deserialize)?;
+                let inputs_json = self
+                    .config
+                    .json_format
+                    .to_string(&inputs)
+                    .map_err(RequestError::Serialize)?;
+                self.publisher
+                    .set_var("inputs", &inputs_json)
+                    .await
+                    .map_err(|e| e.boxed())?;
+            }
+            MessageType::GetVersion => {
+                let version: Version =
+                    serde_json::from_str(payload).map_err(RequestError::Deserialize)?;
+
+                debug!(?version);
+
+                self.publisher
+                    .set_var("sway_version", &version.human_readable)
+                    .await
+                    .map_err(|e| e.boxed())?;
+            }
+            MessageType::RunCommands => {
+                // There is one result for every command in the payload
+                let results: Vec<CommandResult> =
+                    serde_json::from_str(payload).map_err(RequestError::Deserialize)?;
+                let errors = results
+                    .iter()
+                    .filter(|result| !result.success)
+                    .map(|result| {
+                        let error = result.error.as_deref().unwrap_or("unknown error");
+                        if result.parse_error {
+                            warn!("Command could not be parsed: {error}");
+                        } else {
+                            warn!("Command failed: {error}");
+                        }
+                        error.to_owned()
+                    })
+                    .collect::<Vec<_>>();
+                if !errors.is_empty() {
+                    return Err(RequestError::CommandsFailed(errors));
+                }
+            }
+            MessageType::GetBindingState => {
+                let state: BindingState =
+                    serde_json::from_str(payload).map_err(RequestError::Deserialize)?;
+                self.last_mode = Some(state.name.clone());
+                // The binding state doesn't say whether the name is markup, so it is taken as plain text
+                self.publish_mode(state.name, false)
+                    .await
+                    .map_err(|e| e.boxed())?;
+            }
+            MessageType::GetBindingModes => {
+                let modes: Vec<String> =
+                    serde_json::from_str(payload).map_err(RequestError::Deserialize)?;
+                let modes_json = self
+                    .config
+                    .json_format
+                    .to_string(&modes)
+                    .map_err(RequestError::Serialize)?;
+                self.publisher
+                    .set_var("binding_modes", &modes_json)
+                    .await
+                    .map_err(|e| e.boxed())?;
+            }
+            MessageType::Sync => {
+                use serde::Deserialize;
+
+                #[derive(Deserialize, Debug, Clone, Copy)]
+                struct SyncResponse {
+                    pub success: bool,
+                }
+                // Sway doesn't implement the X11 sync and always reports failure,
+                // but the reply still comes after everything we sent before
+                let response: SyncResponse =
+                    serde_json::from_str(payload).map_err(RequestError::Deserialize)?;
+                debug!("Synced with compositor (success: {})", response.success);
+            }
+            MessageType::Subscribe => {
+                use serde::Deserialize;
+
+                #[derive(Deserialize, Debug, Clone, Copy)]
+                struct SubscribeResponse {
+                    pub success: bool,
+                }
+                let response: SubscribeResponse =
+                    serde_json::from_str(payload).map_err(RequestError::Deserialize)?;
+                if response.success {
+                    info!("Successfully subscribed to sway events");
+                    if self.config.emit_on_subscribe_success {
+                        self.publisher
+                            .set_var("sway_update_ready", &true)
+                            .await
+                            .map_err(|e| e.boxed())?;
+                    }
+                } else {
+                    return Err(RequestError::UnsuccessfulSubscription);
+                }
+            }
+            _ => {
+                trace!("{payload_type:?} payload: {payload}")
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Requests the compositor version and publishes it as `sway_version`.
+    pub async fn get_version(&mut self) -> Result<(), RequestError> {
+        self.request(MessageType::GetVersion, None::<String>).await
+    }
+
+    /// Publishes every binding mode the config defines, not just the active one.
+    pub async fn get_binding_modes(&mut self) -> Result<(), RequestError> {
+        self.request(MessageType::GetBindingModes, None::<String>)
+            .await
+    }
+
+    /// Waits until the compositor handled everything we sent before,
+    /// e.g. so that state queried afterwards reflects the commands we ran.
+    pub async fn sync(&mut self) -> Result<(), RequestError> {
+        self.request(MessageType::Sync, None::<String>).await
+    }
+
+    /// Publishes the aggregated urgency of all workspaces and windows.
+    async fn update_urgency(&mut self) -> Result<(), RequestError> {
+        let workspaces = self.urgent_workspaces.clone();
+        let windows = self.urgent_windows.values().cloned().collect::<Vec<_>>();
+        let count = workspaces.len() + windows.len();
+        let urgency = Urgency {
+            any: count > 0,
+            count,
+            workspaces,
+            windows,
+        };
+
+        let urgency_json = self
+            .config
+            .json_format
+            .to_string(&urgency)
+            .map_err(RequestError::Serialize)?;
+        self.publisher
+            .set_var("urgency", &urgency_json)
+            .await
+            .map_err(|e| e.boxed())?;
+        Ok(())
+    }
+
+    /// Sets the active window variables, or defers them until the title settled if title debouncing is enabled.
+    /// They are deferred together, so the bar never shows the app id or icon of one window next to the title of another.
+    async fn set_active_window(
+        &mut self,
+        vars: Vec<(&'static str, String)>,
+    ) -> Result<(), EwwError<Box<dyn Error>>> {
+        if self.config.title_debounce_ms == 0 {
+            self.publisher.set_vars(vars).await.map_err(|e| e.boxed())?;
+        } else {
+            // Every new title pushes the deadline back, so only the last one of a burst is pushed
+            let deadline = Instant::now() + Duration::from_millis(self.config.title_debounce_ms);
+            self.pending_active_window = Some((vars, deadline));
+        }
+        Ok(())
+    }
+
+    /// Publishes the focused window as `active_window` and in the terminal title if enabled.
+    async fn update_active_window(&mut self, window: &Window) -> Result<(), EventError> {
+        if let (Some(path), Some(name)) = (&self.config.terminal_title, window.title()) {
+            // The terminal is only cosmetic, so failures are only logged
+            let res = std::fs::OpenOptions::new()
+                .write(true)
+                .open(path)
+                .and_then(|mut terminal| write_terminal_title(&mut terminal, name));
+            if let Err(e) = res {
+                warn!("Error writing terminal title to {}: {e}", path.display());
+            }
+        }
+
+        let active_window = match self.config.active_window_format {
+            ActiveWindowFormat::String => window.title().map(str::to_owned),
+            ActiveWindowFormat::Json => Some(
+                self.config
+                    .json_format
+                    .to_string(&ActiveWindow::from(window))
+                    .map_err(RequestError::Serialize)?,
+            ),
+        };
+        if let Some(active_window) = active_window {
+            self.active_window_id = Some(window.id);
+
+            let app_id = window.app_id();
+            let mut vars = vec![
+                ("active_window", active_window),
+                ("active_app_id", app_id.unwrap_or_default().to_owned()),
+            ];
+            if !self.config.icons.is_empty() {
+                let icon = self.config.icon(app_id).to_owned();
+                vars.push(("active_window_icon", icon));
+            }
+            self.set_active_window(vars).await?;
+        }
+        Ok(())
+    }
+
+    /// Adds a newly focused window to `window_history`.
+    async fn push_history(&mut self, window: &Window) -> Result<(), RequestError> {
+        let Some(title) = window.title() else {
+            return Ok(());
+        };
+        // Keyed by container id, so different windows with the same title each get an entry
+        if self.config.history_size == 0
+            || self
+                .window_history
+                .front()
+                .is_some_and(|(id, _)| *id == window.id)
+        {
+            return Ok(());
+        }
+        self.window_history
+            .push_front((window.id, title.to_owned()));
+        self.window_history.truncate(self.config.history_size);
+
+        let titles = self
+            .window_history
+            .iter()
+            .map(|(_, title)| title)
+            .collect::<Vec<_>>();
+        let history_json = self
+            .config
+            .json_format
+            .to_string(&titles)
+            .map_err(RequestError::Serialize)?;
+        self.publisher
+            .set_var("window_history", &history_json)
+            .await
+            .map_err(|e| e.boxed())?;
+        Ok(())
+    }
+
+    /// Sets `active_window` to empty once no window is focused anymore.
+    async fn clear_active_window(&mut self) -> Result<(), EwwError<Box<dyn Error>>> {
+        // A title that is still settling belongs to a window that isn't focused anymore
+        self.pending_active_window = None;
+        self.active_window_id = None;
+        self.publisher
+            .set_vars([
+                ("active_window", String::new()),
+                ("active_app_id", String::new()),
+            ])
+            .await
+            .map_err(|e| e.boxed())?;
+        Ok(())
+    }
+
+    /// Pushes the pending active window variables, if there are any.
+    async fn flush_active_window(&mut self) {
+        if let Some((vars, _)) = self.pending_active_window.take() {
+            if let Err(e) = self.publisher.set_vars(vars).await {
+                warn!("Error updating active window: {e}");
+            }
+        }
+    }
+
+    /// Sets `sway_update_alive` to the current time in milliseconds, so the bar can tell that we're still running.
+    async fn beat(&mut self) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        if let Err(e) = self.publisher.set_var("sway_update_alive", &now).await {
+            warn!("Error updating heartbeat: {e}");
+        }
+    }
+
+    /// Requests everything that depends on the workspaces.
+    async fn refresh_workspaces(&mut self) -> Result<(), RequestError> {
+        // This might have changed which windows are visible.
+        // It comes first, since the workspace data includes the app ids of the windows from the tree.
+        self.request(MessageType::GetTree, None::<String>).await?;
+        // We request this, to update our workspace data
+        self.request(MessageType::GetWorkspaces, None::<String>)
+            .await?;
+        // Focusing a workspace might have moved the focus to another output
+        self.request(MessageType::GetOutputs, None::<String>)
+            .await?;
+        if self.config.seat_focus {
+            // This uses the tree we just requested
+            self.request(MessageType::GetSeats, None::<String>).await?;
+        }
+        Ok(())
+    }
+
+    /// Requests the input devices and the seats they are attached to.
+    async fn refresh_inputs(&mut self) -> Result<(), RequestError> {
+        self.request(MessageType::GetInputs, None::<String>).await?;
+        self.request(MessageType::GetSeats, None::<String>).await
+    }
+
+    /// Queries everything the bar shows, so it is correct before the first event arrives.
+    /// This runs on every connection, so nothing from before a reconnect is left over.
+    ///
+    /// Only a failed workspace refresh is returned. The other queries are just logged, since events keep their variables up to date anyway.
+    pub async fn resync(&mut self) -> Result<(), RequestError> {
+        // The version doesn't change while we're connected, so it is only requested here
+        if let Err(e) = self.get_version().await {
+            warn!("Error requesting compositor version: {e}");
+        }
+        // Afterwards, marks are only requested when they change
+        if let Err(e) = self.request(MessageType::GetMarks, None::<String>).await {
+            warn!("Error requesting marks: {e}");
+        }
+        if let Err(e) = self
+            .request(MessageType::GetBindingState, None::<String>)
+            .await
+        {
+            warn!("Error requesting binding state: {e}");
+        }
+        // The modes only change with the config, so they are requested again on reload
+        if let Err(e) = self.get_binding_modes().await {
+            warn!("Error requesting binding modes: {e}");
+        }
+        // Afterwards, the devices are only requested on input events
+        if self.config.inputs {
+            if let Err(e) = self.refresh_inputs().await {
+                warn!("Error requesting input devices: {e}");
+            }
+        }
+        self.refresh_workspaces().await?;
+        // This uses the tree we just requested, so `active_window` is correct before the first focus event
+        if let Err(e) = self.publish_focused_window().await {
+            warn!("Error looking up the focused window: {e}");
+        }
+        Ok(())
+    }
+
+    /// Publishes the focused window of the last tree we received, or clears it if there is none anymore.
+    async fn publish_focused_window(&mut self) -> Result<(), EventError> {
+        let focused = self
+            .tree
+            .as_ref()
+            .and_then(objects::find_focused)
+            .map(Window::from);
+        match focused {
+            Some(window) => self.update_active_window(&window).await,
+            None if self.active_window_id.is_some() => Ok(self.clear_active_window().await?),
+            None => {
+                debug!("No window is focused");
+                Ok(())
+            }
+        }
+    }
+
+    /// Publishes the binding mode as `binding_mode` and `binding_active`,
+    /// along with whether the mode name is pango markup as `binding_mode_markup`.
+    async fn publish_mode(
+        &mut self,
+        mode: String,
+        pango_markup: bool,
+    ) -> Result<bool, EwwError<Infallible>> {
+        let markup = ("binding_mode_markup", pango_markup.to_string());
+        let vars = match &mode[..] {
+            // The user manages binding_active themselves
+            _ if self.config.no_default_mode_reset => vec![("binding_mode", mode), markup],
+            "default" => vec![("binding_active", false.to_string())],
+            _ => vec![
+                ("binding_mode", mode),
+                markup,
+                ("binding_active", true.to_string()),
+            ],
+        };
+
+        // Binding modes are global, but per-output bars might each want their own variables
+        let mut scoped_vars = Vec::new();
+        for (var, val) in &vars {
+            for output in &self.config.mode_outputs {
+                scoped_vars.push((format!("{var}_{}", var_suffix(output)), val.clone()));
+            }
+        }
+        let vars = vars
+            .into_iter()
+            .map(|(var, val)| (var.to_owned(), val))
+            .chain(scoped_vars);
+
+        self.publisher.set_vars(vars).await
+    }
+
+    /// Publishes the layout of the container holding the focused node within the node with the given id as `layout`.
+    ///
+    /// Focus changes come in bursts, so the last tree is reused if it is recent enough and knows the node.
+    async fn update_layout(&mut self, id: usize) -> Result<(), RequestError> {
+        let cached = self
+            .tree_received
+            .is_some_and(|received| received.elapsed() < TREE_CACHE_DURATION)
+            && self.tree.as_ref().is_some_and(|tree| tree.contains(id));
+        if !cached {
+            self.request(MessageType::GetTree, None::<String>).await?;
+        }
+
+        let layout = self
+            .tree
+            .as_ref()
+            .and_then(|tree| tree.layout_of(id))
+            .map(str::to_owned);
+        match layout {
+            Some(layout) => {
+                self.publisher
+                    .set_var("layout", &layout)
+                    .await
+                    .map_err(|e| e.boxed())?;
+            }
+            None => debug!("Node {id} has no layout"),
+        }
+        Ok(())
+    }
+
+    /// Queries the state that would otherwise be reported by events, for compositors whose events are unreliable.
+    async fn poll(&mut self) -> Result<(), EventError> {
+        self.refresh_workspaces().await?;
+        // This uses the tree we just requested
+        self.publish_focused_window().await
+    }
+
+    /// The first eww update that failed since the last call, if failures are configured to stop the daemon.
+    pub fn take_eww_failure(&mut self) -> Option<EwwError<Infallible>> {
+        self.publisher.take_failure()
+    }
+
+    /// Publishes the current state of the workspaces and the tree without waiting for events.
+    pub async fn snapshot(&mut self) -> Result<(), RequestError> {
+        self.resync().await?;
+        // There are no more events to push a pending title on
+        self.flush_active_window().await;
+        Ok(())
+    }
+
+    /// Subscribes to the given events, a JSON array of their names, and handles them until the compositor shuts down.
+    ///
+    /// If no events are configured, nothing is subscribed to and only polling updates the state.
+    pub async fn subscribe_event_loop(
+        &mut self,
+        events: &str,
+        signals: &mut ShutdownSignals,
+    ) -> Result<(), EventLoopError> {
+        info!("Starting event loop");
+
+        if !self.config.events.is_empty() {
+            self.request(MessageType::Subscribe, Some(events)).await?;
+        }
+
+        let mut heartbeat = (self.config.heartbeat_ms > 0).then(|| {
+            let mut interval =
+                tokio::time::interval(Duration::from_millis(self.config.heartbeat_ms));
+            // Beats that were missed while we were busy don't need to be caught up on
+            interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            interval
+        });
+        let mut poll = (self.config.poll_interval_ms > 0).then(|| {
+            let mut interval =
+                tokio::time::interval(Duration::from_millis(self.config.poll_interval_ms));
+            interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            interval
+        });
+
+        loop {
+            // Failures are logged where they happen, but might have to stop us
+            if let Some(failure) = self.publisher.take_failure() {
+                return Err(EventLoopError::EwwFailure(failure));
+            }
+
+            // We wait for the next event, a signal, or a pending window title or workspace refresh to settle
+            let title_deadline = self
+                .pending_active_window
+                .as_ref()
+                .map(|(_, deadline)| *deadline);
+            let workspaces_due = self.workspaces_due;
+            let new_window_ws_due = self.new_window_ws_due;
+            let event = tokio::select! {
+                event = self.frames.event() => event?,
+                _ = tokio::time::sleep_until(title_deadline.unwrap_or_else(Instant::now)), if title_deadline.is_some() => {
+                    self.flush_active_window().await;
+                    continue;
+                }
+                _ = tokio::time::sleep_until(workspaces_due.unwrap_or_else(Instant::now)), if workspaces_due.is_some() => {
+                    self.workspaces_due = None;
+                    if let Err(e) = self.refresh_workspaces().await {
+                        warn!("Error refreshing workspaces: {e}");
+                    }
+                    continue;
+                }
+                _ = tokio::time::sleep_until(new_window_ws_due.unwrap_or_else(Instant::now)), if new_window_ws_due.is_some() => {
+                    self.new_window_ws_due = None;
+                    if let Err(e) = self.publisher.set_var("new_window_ws", &String::new()).await {
+                        warn!("Error clearing new window workspace: {e}");
+                    }
+                    continue;
+                }
+                Some(command) = async {
+                    match &mut self.control {
+                        Some(control) => control.recv().await,
+                        None => std::future::pending().await,
+                    }
+                } => {
+                    if let Err(e) = self.request(MessageType::RunCommands, Some(&command)).await {
+                        warn!("Error running command \"{command}\": {e}");
+                    }
+                    continue;
+                }
+                _ = async {
+                    match &mut heartbeat {
+                        Some(heartbeat) => heartbeat.tick().await,
+                        None => std::future::pending().await,
+                    }
+                } => {
+                    self.beat().await;
+                    continue;
+                }
+                _ = async {
+                    match &mut poll {
+                        Some(poll) => poll.tick().await,
+                        None => std::future::pending().await,
+                    }
+                } => {
+                    if let Err(e) = self.poll().await {
+                        warn!("Error polling the compositor: {e}");
+                    }
+                    continue;
+                }
+                signal = signals.recv() => {
+                    info!("Received {signal}. Shutting down");
+                    break;
+                }
+            };
+
+            info!("Received event of type {:?}", event.event_type);
+            trace!("Message Payload: {}", &event.payload);
+
+            // The event is still handled, e.g. for the logs, but doesn't update eww
+            let muted = self.config.no_update.contains(&event.event_type);
+            self.publisher.set_muted(muted);
+            let res = self.handle_event(event.event_type, event.payload).await;
+            self.publisher.set_muted(false);
+
+            let shutdown = match res {
+                Ok(b) => b,
+                Err(e) => {
+                    warn!("Error occurred during event handling: {e}");
+                    continue;
+                }
+            };
+
+            if shutdown {
+                break;
+            }
+        }
+
+        // Don't lose the final state to an update that is still waiting
+        self.flush_active_window().await;
+        if let Some(failure) = self.publisher.take_failure() {
+            return Err(EventLoopError::EwwFailure(failure));
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip_all,fields(?event_type))]
+    async fn handle_event(
+        &mut self,
+        event_type: EventType,
+        payload: impl AsRef<str>,
+    ) -> Result<bool, EventError> {
+        let payload = payload.as_ref();
+
+        match event_type {
+            EventType::Window => {
+                let response: WindowEvent = serde_json::from_str(payload)?;
+
+                self.publisher
+                    .set_var("active_window_change", &response.change)
+                    .await
+                    .map_err(|e| e.boxed())?;
+
+                let window = &response.container;
+                if window.urgent && response.change != "close" {
+                    let title = window.title().unwrap_or_default().to_owned();
+                    if self.urgent_windows.insert(window.id, title).is_none() {
+                        self.update_urgency().await?;
+                    }
+                } else if self.urgent_windows.remove(&window.id).is_some() {
+                    self.update_urgency().await?;
+                }
+
+                // These change which windows are on the visible workspaces
+                if matches!(&response.change[..], "new" | "close" | "move") {
+                    self.request(MessageType::GetTree, None::<String>).await?;
+                    if self.config.workspace_app_ids {
+                        self.request(MessageType::GetWorkspaces, None::<String>)
+                            .await?;
+                    }
+                }
+                if response.change == "new" {
+                    // The event doesn't say where the window opened, but the tree we just requested does
+                    let workspace = self
+                        .tree
+                        .as_ref()
+                        .and_then(|tree| tree.workspace_of(window.id))
+                        .and_then(|workspace| workspace.name.clone());
+                    if let Some(workspace) = workspace {
+                        self.publisher
+                            .set_var("new_window_ws", &workspace)
+                            .await
+                            .map_err(|e| e.boxed())?;
+                        self.new_window_ws_due = Some(Instant::now() + NEW_WINDOW_WS_DURATION);
+                    }
+                }
+
+                if self.config.seat_focus && response.change == "focus" {
+                    self.request(MessageType::GetTree, None::<String>).await?;
+                    self.request(MessageType::GetSeats, None::<String>).await?;
+                }
+                if response.change == "mark" {
+                    self.request(MessageType::GetMarks, None::<String>).await?;
+                }
+                if self.config.layout && matches!(&response.change[..], "focus" | "floating") {
+                    self.update_layout(window.id).await?;
+                }
+                if response.container.focused {
+                    let marks = match &response.container.marks {
+                        Some(marks) => marks.clone(),
+                        None => {
+                            // The tree has the marks of every window, so we look them up there instead
+                            self.request(MessageType::GetTree, None::<String>).await?;
+                            self.tree
+                                .as_ref()
+                                .and_then(|tree| tree.find(response.container.id))
+                                .map(|window| window.marks.clone())
+                                .unwrap_or_default()
+                        }
+                    };
+                    let marks_json = self
+                        .config
+                        .json_format
+                        .to_string(&marks)
+                        .map_err(RequestError::Serialize)?;
+                    self.publisher
+                        .set_vars([
+                            ("active_window_marks", marks_json),
+                            (
+                                "window_fullscreen",
+                                response.container.is_fullscreen().to_string(),
+                            ),
+                            (
+                                "window_floating",
+                                response.container.is_floating().to_string(),
+                            ),
+                        ])
+                        .await
+                        .map_err(|e| e.boxed())?;
+                }
+
+                match &response.change[..] {
+                    "close" => {
+                        // Closing the last window on a workspace doesn't focus any other window
+                        if response.container.focused
+                            || self.active_window_id == Some(response.container.id)
+                        {
+                            self.clear_active_window().await?;
+                        }
+                    }
+                    // New windows are announced with a focus event of their own once they get focused.
+                    // Other changes, like the title of a window in the background, only matter for the focused window.
+                    "focus" => {
+                        self.update_active_window(&response.container).await?;
+                        self.push_history(&response.container).await?;
+                    }
+                    _ if response.container.focused => {
+                        self.update_active_window(&response.container).await?
+                    }
+                    _ => debug!(
+                        "Ignoring {} event of unfocused window {}",
+                        response.change, response.container.id
+                    ),
+                }
+            }
+            EventType::Workspace => {
+                let response: WorkspaceEvent = serde_json::from_str(payload)?;
+
+                // Push only the focused workspace number first, so the bar can move its highlight
+                // before the full workspace refresh below completes
+                if response.change == WorkspaceEventChange::Focus {
+                    // This is where `workspace back_and_forth` goes. Init events have no old workspace.
+                    if let Some(old) = &response.old {
+                        self.publisher
+                            .set_var("prev_workspace", &old.name)
+                            .await
+                            .map_err(|e| e.boxed())?;
+                    }
+                    if let Some(current) = response.current {
+                        self.publisher
+                            .set_var("ws_focused", &current.num)
+                            .await
+                            .map_err(|e| e.boxed())?;
+                        // There is no window event if there is no window to focus
+                        if current.is_empty() {
+                            self.clear_active_window().await?;
+                        }
+                        if self.config.layout {
+                            self.update_layout(current.id).await?;
+                        }
+                    }
+                }
+
+                // Missing an urgency change leaves ws_info stale, so this doesn't wait for the burst to end
+                if response.change == WorkspaceEventChange::Urgent {
+                    self.request(MessageType::GetWorkspaces, None::<String>)
+                        .await?;
+                }
+
+                // Reloading the config might have renamed workspaces or switched back to the default mode
+                if response.change == WorkspaceEventChange::Reload && self.config.refresh_on_reload
+                {
+                    self.request(MessageType::GetWorkspaces, None::<String>)
+                        .await?;
+                    self.request(MessageType::GetBindingState, None::<String>)
+                        .await?;
+                    self.get_binding_modes().await?;
+                }
+
+                // Moving windows around causes bursts of workspace events,
+                // so the workspaces are only refreshed once the burst is over.
+                // The refresh happens outside of the event, so it is skipped entirely if the event is muted.
+                if !self.config.no_update.contains(&EventType::Workspace) {
+                    self.workspaces_due
+                        .get_or_insert_with(|| Instant::now() + WORKSPACE_REFRESH_DELAY);
+                }
+            }
+            EventType::Shutdown => {
+                let response: ShutdownEvent = serde_json::from_str(payload)?;
+                // i3 also sends this on restart, in which case there is nothing to clean up yet
+                if response.change == "exit" {
+                    if let Some(command) = &self.config.shutdown_command {
+                        // The command is not essential, so failures are only logged
+                        if let Err(e) = run_command(command).await {
+                            warn!("Error running shutdown command {command:?}: {e}");
+                        }
+                    }
+                }
+
+                info!("Shutdown event received. Shutting down");
+                // We want to shutdown this service too if the compositor is shutting down
+                return Ok(true);
+            }
+            EventType::BarConfigUpdate => {
+                let response: BarConfigUpdateEvent = serde_json::from_str(payload)?;
+                let colors_json = self
+                    .config
+                    .json_format
+                    .to_string(&response.colors)
+                    .map_err(RequestError::Serialize)?;
+                self.publisher
+                    .set_vars([
+                        ("bar_id", response.id),
+                        ("bar_mode", response.mode),
+                        (
+                            "bar_hidden_state",
+                            response.hidden_state.unwrap_or_default(),
+                        ),
+                        ("bar_colors", colors_json),
+                    ])
+                    .await
+                    .map_err(|e| e.boxed())?;
+            }
+            EventType::Mode => {
+                let ModeEvent {
+                    change: mode,
+                    pango_markup,
+                } = serde_json::from_str(payload)?;
+
+                // Leaving an ignored mode goes back to the mode the bar still shows, so this is the same as not entering it
+                if mode != "default" && self.config.mode_ignore.contains(&mode) {
+                    debug!("Ignoring mode \"{mode}\"");
+                    return Ok(false);
+                }
+                if self.config.coalesce_modes && self.last_mode.as_ref() == Some(&mode) {
+                    debug!("Mode \"{mode}\" unchanged, skipping update");
+                    return Ok(false);
+                }
+                self.last_mode = Some(mode.clone());
+                self.publish_mode(mode, pango_markup)
+                    .await
+                    .map_err(|e| e.boxed())?;
+            }
+            // The event only describes the device that changed, so we just request the whole list again
+            EventType::Input if self.config.inputs => self.refresh_inputs().await?,
+            _ => {
+                trace!("Received {event_type:?} event with payload: {payload}")
+            }
+        }
+
+        Ok(false)
+    }
+}
+
+/// SIGTERM and SIGINT, which stop the daemon.
+///
+/// Listening for a signal replaces its default handler for the rest of the process,
+/// so this is set up once and shared by all connections and the waits in between.
+pub struct ShutdownSignals {
+    sigterm: Signal,
+    sigint: Signal,
+}
+
+impl ShutdownSignals {
+    pub fn new() -> std::io::Result<Self> {
+        Ok(Self {
+            sigterm: signal(SignalKind::terminate())?,
+            sigint: signal(SignalKind::interrupt())?,
+        })
+    }
+
+    /// Waits for the next signal and returns its name. This is cancel safe, so it can be used in `select!`.
+    pub async fn recv(&mut self) -> &'static str {
+        tokio::select! {
+            _ = self.sigterm.recv() => "SIGTERM",
+            _ = self.sigint.recv() => "SIGINT",
+        }
+    }
+}
+
+/// The read and write halves of a connection to the compositor.
+pub(crate) type Connection = (
+    Box<dyn AsyncRead + Unpin + Send>,
+    Box<dyn AsyncWrite + Unpin + Send>,
+);
+
+/// Connects to the IPC socket at `address`, which is either a Unix socket or a TCP `host:port`.
+pub(crate) async fn connect_socket(
+    address: &str,
+    socket_type: SocketType,
+) -> std::io::Result<Connection> {
+    Ok(match socket_type.resolve(address) {
+        SocketType::Tcp => {
+            let (read_half, write_half) = TcpStream::connect(address).await?.into_split();
+            (Box::new(read_half), Box::new(write_half))
+        }
+        // Auto is already resolved
+        SocketType::Unix | SocketType::Auto => {
+            let (read_half, write_half) = UnixStream::connect(address).await?.into_split();
+            (Box::new(read_half), Box::new(write_half))
+        }
+    })
+}
+
+/// Turns an output name like `DP-1` into something usable in an eww variable name, like `DP_1`.
+fn var_suffix(output: &str) -> String {
+    output
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+/// Sets the title of the terminal behind `out` with an OSC escape sequence.
+pub fn write_terminal_title(out: &mut impl std::io::Write, title: &str) -> std::io::Result<()> {
+    // Control characters in the title could end the sequence early
+    let title = title
+        .chars()
+        .filter(|c| !c.is_control())
+        .collect::<String>();
+    write!(out, "\x1b]0;{title}\x07")?;
+    out.flush()
+}
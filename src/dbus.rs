@@ -0,0 +1,114 @@
+//! Publishing the variables as DBus signals.
+//!
+//! The connection is opened with [`zbus`] on the first update and kept until sending on it fails.
+//! zbus reads everything the bus sends us in the background and drops what nobody subscribed to,
+//! so messages like `NameAcquired` don't pile up on the socket.
+
+use std::{io, path::PathBuf};
+
+use tracing::debug;
+use zbus::{connection::Builder, names::InterfaceName, zvariant::ObjectPath, Connection};
+
+use crate::{
+    config::{Config, DbusBus},
+    output::{Output, OutputFuture},
+};
+
+/// The name of the signal that is emitted for every changed variable
+pub const SIGNAL_NAME: &str = "VarChanged";
+
+/// Emits a signal for every changed variable.
+#[derive(Debug)]
+pub struct DbusOutput {
+    bus: DbusBus,
+    /// The socket of the bus, instead of the one from the environment
+    socket: Option<PathBuf>,
+    path: String,
+    interface: String,
+    connection: Option<Connection>,
+}
+
+impl DbusOutput {
+    /// Sends the signals on the bus from the config, which is looked up in the environment.
+    pub fn new(config: &Config) -> Self {
+        Self {
+            bus: config.dbus_bus,
+            socket: None,
+            path: config.dbus_path.clone(),
+            interface: config.dbus_interface.clone(),
+            connection: None,
+        }
+    }
+
+    /// Sends the signals to the bus listening on the given socket.
+    pub fn with_socket(socket: PathBuf, config: &Config) -> Self {
+        Self {
+            socket: Some(socket),
+            ..Self::new(config)
+        }
+    }
+
+    async fn connect(&self) -> zbus::Result<Connection> {
+        let builder = match (&self.socket, self.bus) {
+            (Some(socket), _) => {
+                Builder::address(format!("unix:path={}", socket.display()).as_str())?
+            }
+            // Like libdbus, zbus falls back to $XDG_RUNTIME_DIR/bus and the default system bus socket
+            (None, DbusBus::Session) => Builder::session()?,
+            (None, DbusBus::System) => Builder::system()?,
+        };
+        debug!("Connecting to the DBus {:?} bus", self.bus);
+        builder.build().await
+    }
+
+    async fn emit(&mut self, vars: &[(String, String)]) -> zbus::Result<()> {
+        // Checked when parsing the config, but the config might have been built directly
+        let path = ObjectPath::try_from(self.path.as_str())?;
+        let interface = InterfaceName::try_from(self.interface.as_str())?;
+
+        let connection = match &self.connection {
+            Some(connection) => connection,
+            None => {
+                let connection = self.connect().await?;
+                self.connection.insert(connection)
+            }
+        };
+        for (var, value) in vars {
+            let sent = connection
+                .emit_signal(None::<()>, &path, &interface, SIGNAL_NAME, &(var, value))
+                .await;
+            if sent.is_err() {
+                // The next update connects again, e.g. once the bus is back up
+                self.connection = None;
+                return sent;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Output for DbusOutput {
+    /// Emits one signal for each variable, carrying its name and value.
+    fn write<'a>(&'a mut self, vars: &'a [(String, String)]) -> OutputFuture<'a, io::Result<bool>> {
+        Box::pin(async move {
+            self.emit(vars)
+                .await
+                .map(|()| true)
+                .map_err(io::Error::other)
+        })
+    }
+
+    fn describe(&self) -> String {
+        format!("DBus signals from {}", self.path)
+    }
+}
+
+/// Whether `path` is a valid DBus object path like `/org/swayupdate/Vars`.
+pub fn is_valid_path(path: &str) -> bool {
+    ObjectPath::try_from(path).is_ok()
+}
+
+/// Whether `interface` is a valid DBus interface name like `org.swayupdate.Vars`.
+pub fn is_valid_interface(interface: &str) -> bool {
+    InterfaceName::try_from(interface).is_ok()
+}
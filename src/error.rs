@@ -1,17 +1,21 @@
-use std::{error::Error, fmt::Debug};
+use std::{convert::Infallible, error::Error, fmt::Debug, path::PathBuf};
 use thiserror::Error;
 
+use crate::message::MessageType;
+
 // ---------------------- Message Error ----------------------
 
 #[derive(Debug, Error)]
 pub enum ResponseDeserializeError {
     #[error("io error while reading response")]
     Io(#[from] std::io::Error),
-    #[error("invalid magic string: \"{0}\", must be \"i3-msg\"")]
+    #[error("invalid magic string: \"{0}\", must be \"i3-ipc\"")]
     InvalidMagicString(String),
-    #[error("invalid message type: {0}")]
+    /// A reply of a type we don't know. The frame was read completely, so the connection can still be used.
+    #[error("invalid message type: {0:#x}")]
     InvalidMessageType(u32),
-    #[error("invalid event type: {0}")]
+    /// An event of a type we don't know. The frame was read completely, so the connection can still be used.
+    #[error("invalid event type: {0:#x}")]
     InvalidEventType(u32),
 }
 
@@ -37,8 +41,14 @@ pub enum EwwError<Err> {
     Io(#[from] std::io::Error),
     #[error("error parsing variable content")]
     ParseVar(Err),
-    #[error("eww executable not found")]
+    #[error("eww executable not found in PATH, its path can be given with --eww-binary")]
     NoEwwExecutable,
+    #[error("{0} is not an executable file")]
+    NotExecutable(PathBuf),
+    #[error("eww reported an error: {0}")]
+    Eww(String),
+    #[error("eww update of {0:?} failed")]
+    UpdateFailed(Vec<String>),
 }
 
 impl<Err> EwwError<Err>
@@ -50,10 +60,39 @@ where
             Self::Io(e) => EwwError::Io(e),
             Self::ParseVar(e) => EwwError::ParseVar(Box::new(e)),
             Self::NoEwwExecutable => EwwError::NoEwwExecutable,
+            Self::NotExecutable(path) => EwwError::NotExecutable(path),
+            Self::Eww(message) => EwwError::Eww(message),
+            Self::UpdateFailed(vars) => EwwError::UpdateFailed(vars),
         }
     }
 }
 
+// ---------------------- Config Error ----------------------
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("unknown option: --{0}")]
+    UnknownOption(String),
+    #[error("missing value for option --{0}")]
+    MissingValue(String),
+    #[error("invalid value for option --{option}: \"{value}\"")]
+    InvalidValue { option: String, value: String },
+    #[error("--subscribe-all can't be combined with a list of events")]
+    SubscribeAllWithEvents,
+    #[error("--output-file can't be combined with --output dbus")]
+    OutputFileWithDbus,
+    #[error("--output dbus needs sway_update to be built with the dbus feature")]
+    DbusDisabled,
+    #[error("error reading {}", .path.display())]
+    ReadFile {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("invalid entry in {} on line {line}", .path.display())]
+    InvalidFile { path: PathBuf, line: usize },
+}
+
 // ---------------------- Event Loop Error ----------------------
 
 #[derive(Debug, Error)]
@@ -64,6 +103,8 @@ pub enum EventLoopError {
     Read(#[from] ResponseDeserializeError),
     #[error("error during event handling")]
     Event(#[from] EventError),
+    #[error("eww failed with --exit-on-eww-failure")]
+    EwwFailure(#[source] EwwError<Infallible>),
 }
 
 // ---------------------- Request Error ----------------------
@@ -82,30 +123,75 @@ pub enum RequestError {
     Serialize(serde_json::error::Error),
     #[error("could not subscribe to event bus")]
     UnsuccessfulSubscription,
+    #[error("no reply to {0:?} request within {1:?}")]
+    Timeout(MessageType, std::time::Duration),
+    #[error("{} of the commands failed: {}", .0.len(), .0.join("; "))]
+    CommandsFailed(Vec<String>),
 }
 
 #[derive(Debug, Error)]
+#[allow(unused)]
 pub enum WorkspaceEventParseError {
     #[error("invalid workspace change: {0}")]
     Invalid(String),
 }
 
+#[derive(Debug, Error)]
+pub enum CommandError {
+    #[error("error running command: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("command exited with {0}")]
+    Failed(std::process::ExitStatus),
+}
+
 #[derive(Debug, Error)]
 pub enum SwayUpdateError {
+    #[error("invalid arguments")]
+    Config(#[from] ConfigError),
+    #[error("error opening log file {}", .path.display())]
+    LogFile {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
     #[error("no events to subscribe to")]
     NoSubscriptionEvents,
+    #[error("error serializing subscription events")]
+    SerializeSubscription(serde_json::error::Error),
     #[error("no active i3/sway ipc socket found")]
     NoSocket,
     #[error("error creating eww instance")]
     Eww(#[from] EwwError<()>),
+    #[error("error running startup command")]
+    StartupCommand(#[from] CommandError),
     #[error("error creating daemon")]
     Daemon(#[from] DaemonError),
     #[error("error in event loop")]
     EventLoop(#[from] EventLoopError),
+    #[error("error querying the current state")]
+    Snapshot(#[source] RequestError),
+    #[error("error sending {0:?} request")]
+    Query(MessageType, #[source] RequestError),
+    #[error("eww failed with --exit-on-eww-failure")]
+    EwwFailure(#[source] EwwError<Infallible>),
+    #[error("error listening for signals")]
+    Signal(#[source] std::io::Error),
+}
+
+impl SwayUpdateError {
+    /// Whether this stops the daemon because of `--exit-on-eww-failure`, so it must not be retried by reconnecting.
+    pub fn is_eww_failure(&self) -> bool {
+        matches!(
+            self,
+            Self::EwwFailure(_) | Self::EventLoop(EventLoopError::EwwFailure(_))
+        )
+    }
 }
 
 #[derive(Debug, Error)]
 pub enum DaemonError {
     #[error("error connecting to unix sockete")]
     Connect(#[from] std::io::Error),
+    #[error("error creating control socket")]
+    ControlSocket(#[source] std::io::Error),
 }
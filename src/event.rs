@@ -2,12 +2,12 @@ use std::str::FromStr;
 
 use enum_primitive::FromPrimitive;
 use serde::Deserialize;
-use tokio::io::{AsyncRead, AsyncReadExt, BufReader};
+use tokio::io::AsyncRead;
 
 use crate::{
     error::{ResponseDeserializeError, WorkspaceEventParseError},
+    message::read_frame,
     objects::{Window, Workspace},
-    HEADER_LENGTH, I3_MAGIC_STRING,
 };
 
 #[derive(PartialEq, Eq, Clone)]
@@ -17,38 +17,17 @@ pub struct Event {
 }
 
 impl Event {
-    pub async fn from_read(read: impl AsyncRead + Unpin) -> Result<Self, ResponseDeserializeError> {
-        let mut reader = BufReader::new(read);
-
-        let header = &mut [0u8; HEADER_LENGTH];
-        // Read the header
-        reader.read_exact(header).await?;
-
-        // Check if the magic string is correct
-        if header[0..6] != I3_MAGIC_STRING {
-            return Err(ResponseDeserializeError::InvalidMagicString(
-                String::from_utf8_lossy(&header[0..6]).to_string(),
-            ));
-        }
-
-        // The first 6 bytes of the header are "i3-msg", so we skip them and read the payload length and type
-        let payload_len = u32::from_ne_bytes(header[6..10].try_into().unwrap()) as usize;
-        let event_type = {
-            let payload_type_int = u32::from_ne_bytes(header[10..14].try_into().unwrap());
-            let reply_type_opt = EventType::from_u32(payload_type_int);
-
-            // Check that the payload type is valid in the reply
-            if let Some(payload_type) = reply_type_opt {
-                payload_type
-            } else {
-                return Err(ResponseDeserializeError::InvalidEventType(payload_type_int));
-            }
-        };
-
-        // Read the actual payload
-        let mut buf = vec![0u8; payload_len];
-        reader.read_exact(&mut buf).await?;
-        let payload = String::from_utf8_lossy(&buf).to_string();
+    /// Reads a single frame from the reader. The reader is not buffered here, so that no bytes of the
+    /// following frame are consumed. Pass a buffered reader that lives as long as the connection instead.
+    ///
+    /// Frames of an unknown event type are still read completely before returning
+    /// [`InvalidEventType`](ResponseDeserializeError::InvalidEventType), so the next frame can be read after it.
+    pub async fn from_read(
+        reader: impl AsyncRead + Unpin,
+    ) -> Result<Self, ResponseDeserializeError> {
+        let (payload_type, payload) = read_frame(reader).await?;
+        let event_type = EventType::from_u32(payload_type)
+            .ok_or(ResponseDeserializeError::InvalidEventType(payload_type))?;
 
         Ok(Self {
             event_type,
@@ -60,7 +39,6 @@ impl Event {
 enum_from_primitive! {
 #[repr(u32)]
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
-#[allow(unused)]
 pub enum EventType {
     Workspace = 0x8000_0000,
     Mode = 0x8000_0002,
@@ -74,7 +52,49 @@ pub enum EventType {
 }
 }
 
-#[derive(Clone, Copy, Debug, Deserialize)]
+impl EventType {
+    pub const ALL: [EventType; 9] = [
+        EventType::Workspace,
+        EventType::Mode,
+        EventType::Window,
+        EventType::BarConfigUpdate,
+        EventType::Binding,
+        EventType::Shutdown,
+        EventType::Tick,
+        EventType::BarStateUpdate,
+        EventType::Input,
+    ];
+
+    /// The name of the event type, as used when subscribing.
+    pub fn name(self) -> &'static str {
+        match self {
+            EventType::Workspace => "workspace",
+            EventType::Mode => "mode",
+            EventType::Window => "window",
+            EventType::BarConfigUpdate => "barconfig_update",
+            EventType::Binding => "binding",
+            EventType::Shutdown => "shutdown",
+            EventType::Tick => "tick",
+            EventType::BarStateUpdate => "bar_state_update",
+            EventType::Input => "input",
+        }
+    }
+}
+
+impl FromStr for EventType {
+    type Err = ();
+
+    /// Parses the name of the event type, see [`EventType::name`].
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|event_type| event_type.name() == s)
+            .ok_or(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum WorkspaceEventChange {
     Init,
     Empty,
@@ -107,7 +127,8 @@ impl FromStr for WorkspaceEventChange {
 pub struct WorkspaceEvent {
     pub change: WorkspaceEventChange,
     pub old: Option<Workspace>,
-    pub current: Workspace,
+    // This is null on reload events
+    pub current: Option<Workspace>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -116,6 +137,21 @@ pub struct WindowEvent {
     pub container: Window,
 }
 
+#[derive(Deserialize, Debug, Clone)]
+pub struct BarConfigUpdateEvent {
+    pub id: String,
+    pub mode: String,
+    pub hidden_state: Option<String>,
+    // This differs between versions, so it is passed through as is
+    #[serde(default)]
+    pub colors: serde_json::Value,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ShutdownEvent {
+    pub change: String,
+}
+
 #[derive(Deserialize, Debug, Clone)]
 pub struct ModeEvent {
     pub change: String,
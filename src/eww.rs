@@ -0,0 +1,207 @@
+use std::{
+    error::Error,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    process::Stdio,
+    str::FromStr,
+    time::Duration,
+};
+
+use tokio::process::Command;
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    config::Config,
+    error::EwwError,
+    output::{Output, OutputFuture},
+};
+
+/// The eww executable, which the variables are published to by default.
+#[derive(Debug, Clone)]
+pub struct EwwOutput {
+    pub binary: String,
+    /// Passed to eww before every subcommand, e.g. to select the config of one of several eww daemons
+    args: Vec<String>,
+    /// How often a failed update is retried
+    retries: u32,
+    /// How long to wait before the first retry. This doubles with every retry.
+    retry_delay: Duration,
+}
+
+impl EwwOutput {
+    /// Uses the eww executable from the config, or looks it up in `PATH` if there is none.
+    pub async fn new(config: &Config) -> Result<Self, EwwError<()>> {
+        if let Some(binary) = &config.eww_binary {
+            if !is_executable(binary) {
+                error!(
+                    "eww executable {} is not an executable file",
+                    binary.display()
+                );
+                return Err(EwwError::NotExecutable(binary.clone()));
+            }
+            return Ok(Self::with_binary(binary.display().to_string(), config));
+        }
+
+        let eww_executable = {
+            let output = match Command::new("which").arg("eww").output().await {
+                Ok(output) => output.stdout,
+                // Minimal systems might not have which, so we search PATH ourselves
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                    debug!("which is not available, searching PATH for eww");
+                    let Some(path) = find_in_path("eww") else {
+                        error!("eww executable not found in PATH");
+                        return Err(EwwError::NoEwwExecutable);
+                    };
+                    return Ok(Self::with_binary(path.display().to_string(), config));
+                }
+                Err(e) => return Err(e.into()),
+            };
+
+            // A path that isn't valid utf8 won't exist after the lossy conversion, so it is reported as not found
+            let eww_path_str = String::from_utf8_lossy(&output).trim_end().to_owned();
+            let eww_path = Path::new(&eww_path_str);
+
+            if !eww_path.exists() {
+                error!("eww executable not found. If it can't be found by \"which\" there is probably something wrong.");
+                return Err(EwwError::NoEwwExecutable);
+            }
+
+            eww_path_str
+        };
+
+        Ok(Self::with_binary(eww_executable, config))
+    }
+
+    /// Uses the given eww executable instead of looking it up.
+    pub fn with_binary(binary: String, config: &Config) -> Self {
+        Self {
+            binary,
+            args: config
+                .eww_config_dir
+                .iter()
+                .flat_map(|dir| ["--config".to_owned(), dir.display().to_string()])
+                .collect(),
+            retries: config.eww_retries,
+            retry_delay: Duration::from_millis(config.eww_retry_ms),
+        }
+    }
+
+    /// Runs `eww update` with the given assignments, retrying if eww exits unsuccessfully.
+    async fn run_update(&self, assignments: &[String]) -> std::io::Result<bool> {
+        let mut delay = self.retry_delay;
+        for attempt in 0..=self.retries {
+            if attempt > 0 {
+                debug!(
+                    "Retrying eww update in {delay:?} (attempt {attempt}/{})",
+                    self.retries
+                );
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+            }
+
+            let success = self
+                .command()
+                .arg("update")
+                .args(assignments)
+                .status()
+                .await?
+                .success();
+            if success {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// An eww invocation with the arguments that come before the subcommand.
+    fn command(&self) -> Command {
+        let mut command = Command::new(&self.binary);
+        command.args(&self.args);
+        command
+    }
+
+    /// Whether the eww daemon is up and answers.
+    pub async fn is_daemon_running(&self) -> bool {
+        self.command()
+            .arg("ping")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .await
+            .is_ok_and(|status| status.success())
+    }
+
+    pub async fn get_var<T: FromStr>(
+        &self,
+        var: &str,
+    ) -> Result<Option<T>, EwwError<<T as FromStr>::Err>>
+    where
+        <T as FromStr>::Err: 'static + Error,
+    {
+        let output = self
+            .command()
+            .arg("get")
+            .arg(var)
+            .output()
+            .await
+            .map_err(EwwError::Io)?;
+
+        // Variables might hold anything, e.g. window titles that aren't valid utf8
+        let out = String::from_utf8_lossy(&output.stdout);
+        let err = String::from_utf8_lossy(&output.stderr);
+        // eww terminates its output with a newline
+        let out = out.trim();
+        let err = err.trim();
+
+        // eww might report errors on either stream
+        let message = if err.is_empty() { out } else { err };
+        if message == format!("Variable not found \"{var}\"") {
+            warn!("Eww variable \"{var}\" not found");
+            Ok(None)
+        } else if !output.status.success() {
+            Err(EwwError::Eww(message.to_owned()))
+        } else {
+            let parsed = T::from_str(out).map_err(EwwError::ParseVar)?;
+
+            info!("Retrieved eww variable \"{var}\"'s value: {}", out);
+            Ok(Some(parsed))
+        }
+    }
+}
+
+impl Output for EwwOutput {
+    fn write<'a>(
+        &'a mut self,
+        vars: &'a [(String, String)],
+    ) -> OutputFuture<'a, std::io::Result<bool>> {
+        Box::pin(async move {
+            let assignments = vars
+                .iter()
+                .map(|(var, val)| format!("{var}={val}"))
+                .collect::<Vec<_>>();
+            self.run_update(&assignments).await
+        })
+    }
+
+    fn is_ready(&self) -> OutputFuture<'_, bool> {
+        Box::pin(self.is_daemon_running())
+    }
+
+    fn describe(&self) -> String {
+        format!("eww at {}", self.binary)
+    }
+}
+
+/// Whether `path` is a file that we're allowed to execute.
+fn is_executable(path: &Path) -> bool {
+    std::fs::metadata(path)
+        .is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+}
+
+/// The first executable called `name` in one of the directories in `PATH`.
+fn find_in_path(name: &str) -> Option<PathBuf> {
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path)
+        .map(|dir| dir.join(name))
+        .find(|candidate| is_executable(candidate))
+}
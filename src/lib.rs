@@ -0,0 +1,338 @@
+//! A daemon that publishes the state of sway to eww variables.
+//!
+//! [`run`] runs the whole daemon for a [`Config`]. The sway IPC protocol, that is the framing of
+//! replies and events and the objects they carry, is available on its own in the other modules.
+
+#[macro_use]
+extern crate enum_primitive;
+
+pub mod backoff;
+pub mod config;
+mod control;
+pub mod daemon;
+#[cfg(feature = "dbus")]
+pub mod dbus;
+pub mod error;
+pub mod event;
+pub mod eww;
+pub mod message;
+pub mod objects;
+pub mod output;
+pub mod publisher;
+pub mod reader;
+
+use std::{collections::HashSet, process::Command};
+
+use backoff::{Backoff, Throttle};
+use config::{Compositor, Config};
+use daemon::{Daemon, ShutdownSignals};
+use error::{CommandError, DaemonError, SwayUpdateError};
+use message::{Message, MessageType};
+use publisher::Publisher;
+use tokio::{io::AsyncWriteExt, time::Duration};
+use tracing::{debug, error, info, warn};
+
+pub const I3_MAGIC_STRING: [u8; 6] = *b"i3-ipc";
+pub const HEADER_LENGTH: usize = 14;
+/// The target the raw frames are logged at with `--verbose-protocol`
+pub const PROTOCOL_TARGET: &str = "sway_update::protocol";
+
+const RECONNECT_BASE_DELAY: Duration = Duration::from_millis(500);
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(30);
+const RECONNECT_HALF_LIFE: Duration = Duration::from_secs(60);
+/// How often we check whether the eww daemon is up when waiting for it
+const EWW_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Runs the daemon with the given configuration until the compositor shuts down,
+/// reconnecting in between if configured to.
+/// Logging is left to the caller, the daemon only emits [`tracing`] events.
+pub async fn run(mut config: Config) -> Result<(), SwayUpdateError> {
+    if let Some(message_type) = config.query {
+        // This doesn't need eww or a subscription
+        return query(&config, message_type).await;
+    }
+    if config.print_socket {
+        return print_sockets(&config);
+    }
+    if config.list_events {
+        // --subscribe-all uses the same list, so this can't drift from what we actually subscribe to
+        for event_type in event::EventType::ALL {
+            println!("{}", event_type.name());
+        }
+        return Ok(());
+    }
+
+    let subscription = {
+        // Without the event loop we don't subscribe to anything, and polling works without events
+        if config.events.is_empty() && !config.once && config.poll_interval_ms == 0 {
+            return Err(SwayUpdateError::NoSubscriptionEvents);
+        };
+        // Sway expects the events as a JSON array of strings
+        serde_json::to_string(&config.events).map_err(SwayUpdateError::SerializeSubscription)?
+    };
+
+    debug!(?subscription, "Enabled Subscriptions");
+
+    // For eww, this checks if it can find an eww instance in your path
+    let mut publisher = Publisher::new(&config).await?;
+    debug!("Publishing variables to {}", publisher.describe());
+
+    if let Some(command) = &config.startup_command {
+        info!("Running startup command {command:?}");
+        if let Err(e) = run_command(command).await {
+            if !config.ignore_startup_failure {
+                error!("Startup command {command:?} failed: {e}");
+                return Err(e.into());
+            }
+            warn!("Startup command {command:?} failed: {e}");
+        }
+    }
+
+    if config.wait_for_eww {
+        wait_for_eww(&publisher, Duration::from_millis(config.wait_for_eww_ms)).await;
+    }
+
+    // This happens only once, so a reconnect doesn't reset what we published before
+    if let Err(e) = publisher.set_vars(config.initial_vars()).await {
+        warn!("Error setting initial variables: {e}");
+    }
+    if let Some(failure) = publisher.take_failure() {
+        return Err(SwayUpdateError::EwwFailure(failure));
+    }
+
+    // Until now, the default handlers just end the process, which is fine since there is nothing to flush yet
+    let mut signals = ShutdownSignals::new().map_err(SwayUpdateError::Signal)?;
+    let mut backoff = Backoff::new(
+        RECONNECT_BASE_DELAY,
+        RECONNECT_MAX_DELAY,
+        RECONNECT_HALF_LIFE,
+    );
+    let mut reconnect_log = Throttle::new(Duration::from_millis(config.reconnect_log_interval_ms));
+    loop {
+        match connect(&mut config, &mut publisher, &subscription, &mut signals).await {
+            // The compositor shut down
+            Ok(()) => return Ok(()),
+            // Reconnecting wouldn't bring eww back
+            Err(e) if e.is_eww_failure() => {
+                error!("{e}");
+                return Err(e);
+            }
+            Err(e) if config.reconnect => {
+                let delay = backoff.failure();
+                // We keep trying during a longer outage, but only say so every now and then
+                match reconnect_log.hit() {
+                    Some(0) => warn!(
+                        "Lost connection to {}: {e}. Reconnecting in {delay:?}",
+                        config.compositor
+                    ),
+                    Some(suppressed) => warn!(
+                        "Lost connection to {}: {e}. Reconnecting in {delay:?} ({suppressed} more failed attempts since the last message)",
+                        config.compositor
+                    ),
+                    None => debug!(
+                        "Lost connection to {}: {e}. Reconnecting in {delay:?}",
+                        config.compositor
+                    ),
+                }
+                tokio::select! {
+                    _ = tokio::time::sleep(delay) => {}
+                    signal = signals.recv() => {
+                        info!("Received {signal} while waiting to reconnect. Shutting down");
+                        return Ok(());
+                    }
+                }
+            }
+            Err(e) => {
+                error!("{e}");
+                return Err(e);
+            }
+        }
+    }
+}
+
+/// Waits until the eww daemon answers, e.g. while it is still starting up alongside us.
+/// Once the timeout is over, we go on anyway, since eww might still come up later.
+async fn wait_for_eww(publisher: &Publisher, timeout: Duration) {
+    let deadline = tokio::time::Instant::now() + timeout;
+    while !publisher.is_ready().await {
+        if tokio::time::Instant::now() >= deadline {
+            warn!("Eww daemon didn't respond within {timeout:?}, continuing anyway");
+            return;
+        }
+        debug!("Waiting for the eww daemon");
+        tokio::time::sleep(EWW_POLL_INTERVAL).await;
+    }
+    info!("Eww daemon is running");
+}
+
+/// Connects to the compositor and handles its events until it shuts down.
+async fn connect(
+    config: &mut Config,
+    publisher: &mut Publisher,
+    subscription: &str,
+    signals: &mut ShutdownSignals,
+) -> Result<(), SwayUpdateError> {
+    let connection = tokio::select! {
+        connection = find_compositor(config) => connection?,
+        signal = signals.recv() => {
+            info!("Received {signal} while connecting. Shutting down");
+            return Ok(());
+        }
+    };
+    let mut daemon = Daemon::with_connection(connection, publisher, config.clone())?;
+
+    if config.once {
+        let snapshot = daemon.snapshot().await;
+        // The failed update might be what the snapshot failed on
+        if let Some(failure) = daemon.take_eww_failure() {
+            return Err(SwayUpdateError::EwwFailure(failure));
+        }
+        return snapshot.map_err(SwayUpdateError::Snapshot);
+    }
+    // After a reconnect, the bar still shows what was true before the connection was lost
+    tokio::select! {
+        resynced = daemon.resync() => if let Err(e) = resynced {
+            warn!("Error querying the current state: {e}");
+        },
+        signal = signals.recv() => {
+            info!("Received {signal} while querying the current state. Shutting down");
+            return Ok(());
+        }
+    }
+
+    let res = daemon.subscribe_event_loop(subscription, signals).await;
+    if let Some(failure) = daemon.take_eww_failure() {
+        return Err(SwayUpdateError::EwwFailure(failure));
+    }
+
+    if let Err(e) = res {
+        error!("Error in event loop: {e}");
+        return Err(e.into());
+    }
+
+    Ok(())
+}
+
+/// Connects to the first socket candidate that accepts, and takes over the compositor it belongs to into `config`.
+///
+/// The socket is looked up on every connection, since it changes when the compositor restarts.
+/// The environment might still point to the socket of an earlier session, so every candidate is tried.
+async fn find_compositor(config: &mut Config) -> Result<daemon::Connection, SwayUpdateError> {
+    let mut error = SwayUpdateError::NoSocket;
+    for (address, detected_compositor, _) in socket_candidates(config) {
+        let mut candidate = config.clone();
+        if candidate.compositor == Compositor::Auto {
+            candidate.compositor = detected_compositor;
+        }
+        debug!("Connecting to {} at {address}", candidate.compositor);
+
+        match daemon::connect_socket(&address, candidate.socket_type).await {
+            Ok(connection) => {
+                info!("Connected to {} at {address}", candidate.compositor);
+                *config = candidate;
+                return Ok(connection);
+            }
+            Err(e) => {
+                debug!("Could not connect to {address}: {e}");
+                error = DaemonError::Connect(e).into();
+            }
+        }
+    }
+    Err(error)
+}
+
+/// Sends a single request and prints the raw reply to stdout.
+async fn query(config: &Config, message_type: MessageType) -> Result<(), SwayUpdateError> {
+    let mut connection = None;
+    for (address, _, _) in socket_candidates(config) {
+        match daemon::connect_socket(&address, config.socket_type).await {
+            Ok(connected) => {
+                debug!("Sending {message_type:?} request to {address}");
+                connection = Some(connected);
+                break;
+            }
+            Err(e) => debug!("Could not connect to {address}: {e}"),
+        }
+    }
+    let (mut reader, mut writer) = connection.ok_or(SwayUpdateError::NoSocket)?;
+
+    let send = async {
+        writer.write_all(&message_type.frame("")).await?;
+        // Nothing is subscribed, so the next frame is the reply
+        Ok(Message::from_read(&mut reader).await?)
+    };
+    let reply = send
+        .await
+        .map_err(|e| SwayUpdateError::Query(message_type, e))?;
+    println!("{}", reply.payload);
+    Ok(())
+}
+
+/// Prints the sockets we would try to connect to, in order, along with where we found them.
+fn print_sockets(config: &Config) -> Result<(), SwayUpdateError> {
+    let mut found = false;
+    for (address, _, source) in socket_candidates(config) {
+        println!("{address}\t{source}");
+        found = true;
+    }
+    if !found {
+        return Err(SwayUpdateError::NoSocket);
+    }
+    Ok(())
+}
+
+/// The sockets to try connecting to, in order: the one given in the config, or the ones of the running compositor.
+/// Each comes with the compositor it belongs to and where we found it.
+fn socket_candidates(
+    config: &Config,
+) -> Box<dyn Iterator<Item = (String, Compositor, &'static str)>> {
+    if let Some(socket) = &config.socket {
+        return Box::new(std::iter::once((
+            socket.clone(),
+            Compositor::Sway,
+            "--socket",
+        )));
+    }
+
+    let swaysock = std::env::var("SWAYSOCK").ok();
+    let mut candidates = Vec::new();
+    if let Ok(addr) = std::env::var("I3SOCK") {
+        // Sway sets I3SOCK too for compatibility, so only I3SOCK being set means we're running under i3
+        let compositor = if swaysock.is_some() {
+            Compositor::Sway
+        } else {
+            Compositor::I3
+        };
+        candidates.push((addr, compositor, "I3SOCK"));
+    }
+    candidates.extend(swaysock.map(|addr| (addr, Compositor::Sway, "SWAYSOCK")));
+
+    // Sway is only asked if none of the environment variables lead anywhere
+    let sway = std::iter::once_with(|| {
+        let out = Command::new("sway").arg("--get-socketpath").output().ok()?;
+        let addr = String::from_utf8_lossy(&out.stdout).trim().to_string();
+        Some((addr, Compositor::Sway, "sway --get-socketpath"))
+    })
+    .flatten();
+
+    let mut seen = HashSet::new();
+    Box::new(
+        candidates
+            .into_iter()
+            .chain(sway)
+            .filter(move |(addr, _, _)| !addr.is_empty() && seen.insert(addr.clone())),
+    )
+}
+
+/// Runs a command given as its arguments and waits for it to finish.
+pub(crate) async fn run_command(argv: &[String]) -> Result<(), CommandError> {
+    let status = tokio::process::Command::new(&argv[0])
+        .args(&argv[1..])
+        .status()
+        .await?;
+    if !status.success() {
+        return Err(CommandError::Failed(status));
+    }
+    debug!("Command {argv:?} finished successfully");
+    Ok(())
+}
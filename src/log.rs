@@ -0,0 +1,106 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    path::PathBuf,
+    sync::Mutex,
+};
+
+use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+
+use sway_update::{config::Config, error::SwayUpdateError, PROTOCOL_TARGET};
+
+/// Sets up logging to stderr and, if configured, to a log file. Both honor `RUST_LOG`.
+///
+/// The raw frames are only logged with `--verbose-protocol`, even if `RUST_LOG` enables tracing otherwise.
+pub fn init(config: &Config) -> Result<(), SwayUpdateError> {
+    let log_file = config
+        .log_file
+        .as_ref()
+        .map(|path| {
+            LogFile::open(path.clone(), config.log_file_max_bytes).map_err(|source| {
+                SwayUpdateError::LogFile {
+                    path: path.clone(),
+                    source,
+                }
+            })
+        })
+        .transpose()?;
+
+    let stderr_layer = (log_file.is_none() || !config.log_file_only)
+        .then(|| fmt::layer().with_writer(std::io::stderr).without_time());
+    // Unlike stderr, which usually ends up in a journal, nothing timestamps the file for us
+    let file_layer = log_file.map(|log_file| {
+        fmt::layer()
+            .with_writer(Mutex::new(log_file))
+            .with_ansi(false)
+    });
+
+    let level = if config.verbose_protocol {
+        "trace"
+    } else {
+        "off"
+    };
+    let filter = EnvFilter::from_default_env()
+        .add_directive(format!("{PROTOCOL_TARGET}={level}").parse().unwrap());
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(stderr_layer)
+        .with(file_layer)
+        .init();
+
+    Ok(())
+}
+
+/// A log file that is rotated once it grows beyond a maximum size.
+/// The previous log is kept with a `.1` suffix.
+struct LogFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    max_size: Option<u64>,
+}
+
+impl LogFile {
+    fn open(path: PathBuf, max_size: Option<u64>) -> std::io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            path,
+            file,
+            size,
+            max_size,
+        })
+    }
+
+    fn rotate(&mut self) -> std::io::Result<()> {
+        let mut rotated = self.path.clone().into_os_string();
+        rotated.push(".1");
+        std::fs::rename(&self.path, rotated)?;
+
+        self.file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl Write for LogFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if let Some(max_size) = self.max_size {
+            if self.size > 0 && self.size + buf.len() as u64 > max_size {
+                self.rotate()?;
+            }
+        }
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.file.flush()
+    }
+}
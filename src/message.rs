@@ -1,7 +1,13 @@
+use std::str::FromStr;
+
 use enum_primitive::FromPrimitive;
-use tokio::io::{AsyncRead, AsyncReadExt, BufReader};
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tracing::trace;
+
+use crate::{error::ResponseDeserializeError, HEADER_LENGTH, I3_MAGIC_STRING, PROTOCOL_TARGET};
 
-use crate::{error::ResponseDeserializeError, HEADER_LENGTH, I3_MAGIC_STRING};
+/// How much of each payload is logged at the protocol target
+const MAX_LOGGED_PAYLOAD_BYTES: usize = 256;
 
 #[derive(Clone)]
 pub struct Message {
@@ -9,41 +15,66 @@ pub struct Message {
     pub payload: String,
 }
 
+/// Reads a single frame from the reader and returns its raw type along with the payload.
+///
+/// The whole frame is read even if its type turns out to be unknown, so the next frame can be read after it.
+pub(crate) async fn read_frame(
+    mut reader: impl AsyncRead + Unpin,
+) -> Result<(u32, String), ResponseDeserializeError> {
+    let header = &mut [0u8; HEADER_LENGTH];
+    // Read the header
+    reader.read_exact(header).await?;
+
+    // Check if the magic string is correct
+    if header[0..6] != I3_MAGIC_STRING {
+        return Err(ResponseDeserializeError::InvalidMagicString(
+            String::from_utf8_lossy(&header[0..6]).to_string(),
+        ));
+    }
+
+    // The first 6 bytes of the header are "i3-ipc", so we skip them and read the payload length and type
+    let payload_len = u32::from_ne_bytes(header[6..10].try_into().unwrap()) as usize;
+    let payload_type = u32::from_ne_bytes(header[10..14].try_into().unwrap());
+
+    // Read the actual payload
+    let mut buf = vec![0u8; payload_len];
+    reader.read_exact(&mut buf).await?;
+    trace_frame(header, &buf);
+
+    Ok((payload_type, String::from_utf8_lossy(&buf).to_string()))
+}
+
+/// Logs the raw bytes of a frame at the protocol target, for debugging a desynced connection.
+fn trace_frame(header: &[u8; HEADER_LENGTH], payload: &[u8]) {
+    if !tracing::enabled!(target: PROTOCOL_TARGET, tracing::Level::TRACE) {
+        return;
+    }
+    let hex = header
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let len = u32::from_ne_bytes(header[6..10].try_into().unwrap());
+    let frame_type = u32::from_ne_bytes(header[10..14].try_into().unwrap());
+    let shown = &payload[..payload.len().min(MAX_LOGGED_PAYLOAD_BYTES)];
+    trace!(
+        target: PROTOCOL_TARGET,
+        header = hex,
+        len,
+        frame_type = %format_args!("{frame_type:#x}"),
+        payload = %String::from_utf8_lossy(shown),
+        "frame"
+    );
+}
+
 impl Message {
-    pub async fn from_read(read: impl AsyncRead + Unpin) -> Result<Self, ResponseDeserializeError> {
-        let mut reader = BufReader::new(read);
-
-        let header = &mut [0u8; HEADER_LENGTH];
-        // Read the header
-        reader.read_exact(header).await?;
-
-        // Check if the magic string is correct
-        if header[0..6] != I3_MAGIC_STRING {
-            return Err(ResponseDeserializeError::InvalidMagicString(
-                String::from_utf8_lossy(&header[0..6]).to_string(),
-            ));
-        }
-
-        // The first 6 bytes of the header are "i3-msg", so we skip them and read the payload length and type
-        let payload_len = u32::from_ne_bytes(header[6..10].try_into().unwrap()) as usize;
-        let message_type = {
-            let payload_type_int = u32::from_ne_bytes(header[10..14].try_into().unwrap());
-            let reply_type_opt = MessageType::from_u32(payload_type_int);
-
-            // Check that the payload type is valid in the reply
-            if let Some(payload_type) = reply_type_opt {
-                payload_type
-            } else {
-                return Err(ResponseDeserializeError::InvalidMessageType(
-                    payload_type_int,
-                ));
-            }
-        };
-
-        // Read the actual payload
-        let mut buf = vec![0u8; payload_len];
-        reader.read_exact(&mut buf).await?;
-        let payload = String::from_utf8_lossy(&buf).to_string();
+    /// Reads a single reply from the reader. See [`Event::from_read`](crate::event::Event::from_read) on buffering.
+    pub async fn from_read(
+        reader: impl AsyncRead + Unpin,
+    ) -> Result<Self, ResponseDeserializeError> {
+        let (payload_type, payload) = read_frame(reader).await?;
+        let message_type = MessageType::from_u32(payload_type)
+            .ok_or(ResponseDeserializeError::InvalidMessageType(payload_type))?;
 
         Ok(Self {
             message_type,
@@ -83,4 +114,41 @@ impl MessageType {
     pub fn bytes(self) -> std::array::IntoIter<u8, 4> {
         self.as_bytes().into_iter()
     }
+
+    /// Frames a request of this type with the given payload.
+    pub fn frame(self, payload: &str) -> Vec<u8> {
+        I3_MAGIC_STRING
+            .into_iter()
+            .chain((payload.len() as u32).to_ne_bytes())
+            .chain(self.bytes())
+            .chain(payload.bytes())
+            .collect()
+    }
+}
+
+impl FromStr for MessageType {
+    type Err = ();
+
+    /// Parses the snake case name of the message type, e.g. `get_workspaces`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use MessageType::*;
+        Ok(match s {
+            "run_commands" => RunCommands,
+            "get_workspaces" => GetWorkspaces,
+            "subscribe" => Subscribe,
+            "get_outputs" => GetOutputs,
+            "get_tree" => GetTree,
+            "get_marks" => GetMarks,
+            "get_bar_config" => GetBarConfig,
+            "get_version" => GetVersion,
+            "get_binding_modes" => GetBindingModes,
+            "get_config" => GetConfig,
+            "send_tick" => SendTick,
+            "sync" => Sync,
+            "get_binding_state" => GetBindingState,
+            "get_inputs" => GetInputs,
+            "get_seats" => GetSeats,
+            _ => return Err(()),
+        })
+    }
 }
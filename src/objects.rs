@@ -5,11 +5,26 @@ pub struct Workspace {
     pub id: usize,
     pub num: isize,
     pub name: String,
+    #[serde(default)]
     pub output: String,
+    #[serde(default)]
     pub focused: bool,
+    #[serde(default)]
     pub urgent: bool,
     // This might not exist in workspace change events
     pub visible: Option<bool>,
+    // Only workspace events include the contents of the workspace
+    #[serde(default)]
+    pub nodes: Vec<Node>,
+    #[serde(default)]
+    pub floating_nodes: Vec<Node>,
+}
+
+impl Workspace {
+    /// Whether there are no windows on the workspace. Only meaningful for workspaces from workspace events.
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty() && self.floating_nodes.is_empty()
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
@@ -20,6 +35,33 @@ pub struct WorkspaceInfo {
     pub urgent: bool,
     pub visible: bool,
     pub active: bool,
+    /// The output showing the workspace, or empty if it isn't visible
+    #[serde(default)]
+    pub shown_on: String,
+    // Only emitted if enabled, since it requires walking the whole tree
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app_ids: Option<Vec<String>>,
+}
+
+impl From<Workspace> for WorkspaceInfo {
+    fn from(workspace: Workspace) -> Self {
+        let visible = workspace.visible.unwrap_or_default();
+        Self {
+            name: workspace.name,
+            num: workspace.num,
+            // All workspaces we can get from the get_workspace command are active workspaces
+            active: true,
+            focused: workspace.focused,
+            urgent: workspace.urgent,
+            visible,
+            shown_on: if visible {
+                workspace.output
+            } else {
+                String::new()
+            },
+            app_ids: None,
+        }
+    }
 }
 
 impl WorkspaceInfo {
@@ -30,14 +72,467 @@ impl WorkspaceInfo {
             ..Default::default()
         }
     }
+
+    /// The most relevant state of this workspace as a single word.
+    pub fn state(&self) -> &'static str {
+        if self.focused {
+            "focused"
+        } else if self.urgent {
+            "urgent"
+        } else if self.visible {
+            "visible"
+        } else if self.active {
+            "active"
+        } else {
+            "inactive"
+        }
+    }
+
+    /// The numbers of the workspaces, sorted and separated by commas like `1,3,5`.
+    /// Named workspaces without a number are left out.
+    pub fn list(workspaces: &[WorkspaceInfo]) -> String {
+        let mut nums = workspaces
+            .iter()
+            .map(|workspace| workspace.num)
+            .filter(|&num| num >= 0)
+            .collect::<Vec<_>>();
+        nums.sort_unstable();
+        nums.dedup();
+        nums.iter()
+            .map(isize::to_string)
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+}
+
+/// `ws_info` along with the version of its format, so consumers can tell when the fields change.
+#[derive(Serialize, Debug, Clone)]
+pub struct WorkspaceInfoEnvelope<'a> {
+    pub version: u32,
+    pub workspaces: &'a [WorkspaceInfo],
+}
+
+impl<'a> WorkspaceInfoEnvelope<'a> {
+    /// Bumped whenever fields of [`WorkspaceInfo`] are changed or removed
+    pub const VERSION: u32 = 1;
+
+    pub fn new(workspaces: &'a [WorkspaceInfo]) -> Self {
+        Self {
+            version: Self::VERSION,
+            workspaces,
+        }
+    }
 }
 
 #[derive(Deserialize, Debug, Clone)]
 pub struct Window {
     pub id: usize,
     pub name: Option<String>,
+    #[serde(default)]
     pub focused: bool,
+    #[serde(default)]
     pub urgent: bool,
+    // i3 neither reports a pid nor an app id
     pub pid: Option<usize>,
     pub app_id: Option<String>,
+    // Only X11 windows (i3 and Xwayland) have these
+    pub window_properties: Option<WindowProperties>,
+    // Older versions of i3 leave this out of window events
+    pub marks: Option<Vec<String>>,
+    /// 0 if not fullscreen, 1 if fullscreen on the output and 2 if fullscreen globally
+    pub fullscreen_mode: Option<u8>,
+    #[serde(rename = "type")]
+    pub node_type: Option<NodeType>,
+    // i3 reports floating with this instead of the node type, e.g. "auto_on" or "user_off"
+    pub floating: Option<String>,
+}
+
+impl Window {
+    pub fn is_fullscreen(&self) -> bool {
+        self.fullscreen_mode.unwrap_or(0) > 0
+    }
+
+    pub fn is_floating(&self) -> bool {
+        self.node_type == Some(NodeType::FloatingCon)
+            || self
+                .floating
+                .as_deref()
+                .is_some_and(|floating| floating.ends_with("_on"))
+    }
+
+    /// The app id of the window, falling back to the X11 class for i3 and Xwayland windows.
+    pub fn app_id(&self) -> Option<&str> {
+        self.app_id
+            .as_deref()
+            .or_else(|| self.window_properties.as_ref()?.class.as_deref())
+    }
+
+    /// The title to show for the window. Some apps set blank titles, so this falls back to the
+    /// X11 title property and then to the app id instead of returning an empty title.
+    pub fn title(&self) -> Option<&str> {
+        let non_blank = |title: &str| !title.trim().is_empty();
+        self.name
+            .as_deref()
+            .filter(|name| non_blank(name))
+            .or_else(|| {
+                self.window_properties
+                    .as_ref()?
+                    .title
+                    .as_deref()
+                    .filter(|title| non_blank(title))
+            })
+            .or_else(|| self.app_id().filter(|app_id| non_blank(app_id)))
+    }
+}
+
+/// The focused window, as published in `active_window`.
+#[derive(Serialize, Debug, Clone)]
+pub struct ActiveWindow {
+    pub name: Option<String>,
+    pub app_id: Option<String>,
+    pub pid: Option<usize>,
+    pub urgent: bool,
+    pub focused: bool,
+}
+
+impl From<&Window> for ActiveWindow {
+    fn from(window: &Window) -> Self {
+        Self {
+            name: window.title().map(str::to_owned),
+            app_id: window.app_id().map(str::to_owned),
+            pid: window.pid,
+            urgent: window.urgent,
+            focused: window.focused,
+        }
+    }
+}
+
+impl From<&Node> for Window {
+    fn from(node: &Node) -> Self {
+        Self {
+            id: node.id,
+            name: node.name.clone(),
+            focused: node.focused,
+            urgent: node.urgent,
+            pid: node.pid,
+            app_id: node.app_id.clone(),
+            window_properties: node.window_properties.clone(),
+            marks: Some(node.marks.clone()),
+            fullscreen_mode: node.fullscreen_mode,
+            node_type: Some(node.node_type),
+            floating: node.floating.clone(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct WindowProperties {
+    pub class: Option<String>,
+    pub instance: Option<String>,
+    pub title: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Output {
+    pub name: String,
+    pub active: bool,
+    #[serde(default)]
+    pub primary: bool,
+    #[serde(default)]
+    pub focused: bool,
+    // Disabled outputs have no scale and no workspace
+    pub scale: Option<f64>,
+    pub current_workspace: Option<String>,
+    pub current_mode: Option<OutputMode>,
+    pub rect: Rect,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct OutputMode {
+    pub width: i32,
+    pub height: i32,
+    /// The refresh rate in mHz
+    pub refresh: i32,
+}
+
+/// All urgency signals, aggregated from the workspaces and windows.
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct Urgency {
+    pub any: bool,
+    pub count: usize,
+    pub workspaces: Vec<isize>,
+    pub windows: Vec<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Version {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+    pub human_readable: String,
+}
+
+/// The reply to `GetBindingState`.
+#[derive(Deserialize, Debug, Clone)]
+pub struct BindingState {
+    /// The name of the current binding mode
+    pub name: String,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum NodeType {
+    Root,
+    Output,
+    Workspace,
+    Con,
+    FloatingCon,
+    // i3 has some more, like dockareas
+    #[serde(other)]
+    Other,
+}
+
+/// A node of the tree returned by `GetTree`.
+#[derive(Deserialize, Debug, Clone)]
+pub struct Node {
+    pub id: usize,
+    pub name: Option<String>,
+    #[serde(rename = "type")]
+    pub node_type: NodeType,
+    #[serde(default)]
+    pub focused: bool,
+    #[serde(default)]
+    pub urgent: bool,
+    // Only workspaces report these
+    pub visible: Option<bool>,
+    pub num: Option<isize>,
+    // Only outputs report this
+    pub current_workspace: Option<String>,
+    pub pid: Option<usize>,
+    pub app_id: Option<String>,
+    pub window_properties: Option<WindowProperties>,
+    #[serde(default)]
+    pub marks: Vec<String>,
+    pub fullscreen_mode: Option<u8>,
+    pub floating: Option<String>,
+    /// How the children are arranged, e.g. `splith` or `tabbed`
+    pub layout: Option<String>,
+    /// The ids of the children, the most recently focused first
+    #[serde(default)]
+    pub focus: Vec<usize>,
+    #[serde(default)]
+    pub nodes: Vec<Node>,
+    #[serde(default)]
+    pub floating_nodes: Vec<Node>,
+}
+
+impl Node {
+    /// The name of the output holding the scratchpad workspace
+    pub const SCRATCHPAD_OUTPUT: &'static str = "__i3";
+    /// The name of the workspace holding the scratchpad windows
+    pub const SCRATCHPAD_WORKSPACE: &'static str = "__i3_scratch";
+
+    /// The app id of the window, falling back to the X11 class for i3 and Xwayland windows.
+    pub fn app_id(&self) -> Option<&str> {
+        self.app_id
+            .as_deref()
+            .or_else(|| self.window_properties.as_ref()?.class.as_deref())
+    }
+
+    /// The tiling and floating children of this node.
+    pub fn children(&self) -> impl Iterator<Item = &Node> {
+        self.nodes.iter().chain(self.floating_nodes.iter())
+    }
+
+    /// Whether this node is a window, i.e. a container without children.
+    pub fn is_window(&self) -> bool {
+        matches!(self.node_type, NodeType::Con | NodeType::FloatingCon)
+            && self.nodes.is_empty()
+            && self.floating_nodes.is_empty()
+    }
+
+    /// All windows below this node.
+    pub fn windows(&self) -> Vec<&Node> {
+        if self.is_window() {
+            return vec![self];
+        }
+        self.children().flat_map(Node::windows).collect()
+    }
+
+    /// All workspaces below this node. In i3 these are not direct children of the outputs.
+    pub fn workspaces(&self) -> Vec<&Node> {
+        if self.node_type == NodeType::Workspace {
+            return vec![self];
+        }
+        self.children().flat_map(Node::workspaces).collect()
+    }
+
+    /// Whether the node with the given id is this node or below it.
+    pub fn contains(&self, id: usize) -> bool {
+        self.id == id || self.children().any(|child| child.contains(id))
+    }
+
+    /// The node with the given id, if it is this node or below it.
+    pub fn find(&self, id: usize) -> Option<&Node> {
+        if self.id == id {
+            return Some(self);
+        }
+        self.children().find_map(|child| child.find(id))
+    }
+
+    /// The node whose child is the node with the given id.
+    pub fn parent_of(&self, id: usize) -> Option<&Node> {
+        if self.children().any(|child| child.id == id) {
+            return Some(self);
+        }
+        self.children().find_map(|child| child.parent_of(id))
+    }
+
+    /// The node that has focus within this one, following the focus order down to a node without children.
+    pub fn focused_descendant(&self) -> &Node {
+        self.focus
+            .first()
+            .and_then(|&id| self.children().find(|child| child.id == id))
+            .map_or(self, Node::focused_descendant)
+    }
+
+    /// The layout of the container that holds the focused node within the node with the given id.
+    /// Floating windows report `floating`, and empty workspaces report their own layout.
+    pub fn layout_of(&self, id: usize) -> Option<&str> {
+        let node = self.find(id)?.focused_descendant();
+        if node.node_type == NodeType::Workspace {
+            return node.layout.as_deref();
+        }
+        let parent = self.parent_of(node.id)?;
+        // In i3 floating windows are wrapped in a floating container, in sway they are one themselves
+        let floating = node.node_type == NodeType::FloatingCon
+            || parent.node_type == NodeType::FloatingCon
+            || parent
+                .floating_nodes
+                .iter()
+                .any(|child| child.id == node.id);
+        if floating {
+            Some("floating")
+        } else {
+            parent.layout.as_deref()
+        }
+    }
+
+    /// The workspace containing the node with the given id.
+    pub fn workspace_of(&self, id: usize) -> Option<&Node> {
+        self.workspaces()
+            .into_iter()
+            .find(|workspace| workspace.contains(id))
+    }
+
+    /// The name of the output containing the node with the given id.
+    pub fn output_of(&self, id: usize) -> Option<&str> {
+        self.children()
+            .filter(|output| output.node_type == NodeType::Output)
+            .find(|output| output.contains(id))?
+            .name
+            .as_deref()
+    }
+
+    /// Counts the windows stashed in the scratchpad. Every one of them is a floating child of the scratchpad workspace.
+    pub fn scratchpad_count(&self) -> usize {
+        self.workspaces()
+            .into_iter()
+            .find(|workspace| workspace.name.as_deref() == Some(Self::SCRATCHPAD_WORKSPACE))
+            .map_or(0, |scratchpad| scratchpad.floating_nodes.len())
+    }
+
+    /// Counts the windows on all visible workspaces, excluding the scratchpad.
+    pub fn visible_window_count(&self) -> usize {
+        self.children()
+            .filter(|output| output.node_type == NodeType::Output)
+            .filter(|output| output.name.as_deref() != Some(Self::SCRATCHPAD_OUTPUT))
+            .flat_map(|output| {
+                output.workspaces().into_iter().filter(|workspace| {
+                    workspace.visible.unwrap_or(false)
+                        || workspace.name.is_some() && workspace.name == output.current_workspace
+                })
+            })
+            .map(|workspace| workspace.windows().len())
+            .sum()
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Seat {
+    pub name: String,
+    /// The id of the node focused by this seat, 0 if nothing is focused
+    #[serde(default)]
+    pub focus: usize,
+    /// The input devices attached to this seat
+    #[serde(default)]
+    pub devices: Vec<Input>,
+}
+
+/// An input device. Sway reports a lot more about each device, like its keyboard layouts and libinput settings,
+/// but only what identifies it is kept.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Input {
+    pub identifier: String,
+    #[serde(default)]
+    pub name: String,
+    /// Like `keyboard`, `pointer`, `touchpad` or `switch`
+    #[serde(rename = "type", default)]
+    pub input_type: String,
+}
+
+/// The input devices of a single seat.
+#[derive(Serialize, Debug, Clone)]
+pub struct SeatInputs {
+    pub name: String,
+    /// The identifiers of the devices, which are listed in full in `inputs`
+    pub devices: Vec<String>,
+}
+
+impl From<Seat> for SeatInputs {
+    fn from(seat: Seat) -> Self {
+        Self {
+            name: seat.name,
+            devices: seat
+                .devices
+                .into_iter()
+                .map(|device| device.identifier)
+                .collect(),
+        }
+    }
+}
+
+/// What a single seat has focused.
+#[derive(Serialize, Debug, Clone)]
+pub struct SeatFocus {
+    pub name: String,
+    pub focus: usize,
+    pub output: Option<String>,
+}
+
+/// The result of a single command sent with `RunCommands`.
+#[derive(Deserialize, Debug, Clone)]
+pub struct CommandResult {
+    pub success: bool,
+    pub error: Option<String>,
+    /// Whether the command couldn't be parsed, as opposed to failing when it ran
+    #[serde(default)]
+    pub parse_error: bool,
+}
+
+/// The focused window below `node`. If a workspace or a container with several windows is focused,
+/// there is no focused window.
+pub fn find_focused(node: &Node) -> Option<&Node> {
+    if node.focused {
+        return node.is_window().then_some(node);
+    }
+    node.children().find_map(find_focused)
 }
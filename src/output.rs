@@ -0,0 +1,66 @@
+//! Where the variables end up. This is eww unless configured otherwise.
+
+use std::{collections::BTreeMap, fmt::Debug, future::Future, path::PathBuf, pin::Pin};
+
+/// What the methods of [`Output`] return. The future is boxed, so the output can be chosen at runtime.
+pub type OutputFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Something the variables are published to, like eww or a file.
+///
+/// Skipping unchanged values, resyncing and muting happen in [`Publisher`](crate::publisher::Publisher),
+/// so an output only has to deliver what it is given.
+pub trait Output: Debug + Send {
+    /// Sets all of the given variables at once. Returns whether the output took them.
+    fn write<'a>(
+        &'a mut self,
+        vars: &'a [(String, String)],
+    ) -> OutputFuture<'a, std::io::Result<bool>>;
+
+    /// Whether the output can take variables yet. Outputs that don't need another process to listen always can.
+    fn is_ready(&self) -> OutputFuture<'_, bool> {
+        Box::pin(std::future::ready(true))
+    }
+
+    /// Where the variables go, for the logs.
+    fn describe(&self) -> String;
+}
+
+/// A JSON file holding the current value of every variable.
+#[derive(Debug)]
+pub struct FileOutput {
+    path: PathBuf,
+    values: BTreeMap<String, String>,
+}
+
+impl FileOutput {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            values: BTreeMap::new(),
+        }
+    }
+}
+
+impl Output for FileOutput {
+    /// Replaces the file with one that has the new values, so readers either see all of them or none.
+    fn write<'a>(
+        &'a mut self,
+        vars: &'a [(String, String)],
+    ) -> OutputFuture<'a, std::io::Result<bool>> {
+        Box::pin(async move {
+            self.values.extend(vars.iter().cloned());
+            let json = serde_json::to_string(&self.values).map_err(std::io::Error::other)?;
+
+            // The temporary file has to be on the same file system for the rename to be atomic
+            let mut temp = self.path.clone().into_os_string();
+            temp.push(".tmp");
+            tokio::fs::write(&temp, json).await?;
+            tokio::fs::rename(&temp, &self.path).await?;
+            Ok(true)
+        })
+    }
+
+    fn describe(&self) -> String {
+        format!("file {}", self.path.display())
+    }
+}
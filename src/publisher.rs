@@ -0,0 +1,202 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    convert::Infallible,
+    str::FromStr,
+};
+
+use tracing::{info, trace, warn};
+
+use crate::{
+    config::{Config, OutputBackend},
+    error::EwwError,
+    eww::EwwOutput,
+    output::{FileOutput, Output},
+};
+
+/// The target every variable update is logged at, so they can be filtered with `RUST_LOG=sway_update::eww::vars=info`.
+/// It is named after eww, which used to be the only output, so existing filters keep working.
+pub const VARS_TARGET: &str = "sway_update::eww::vars";
+/// Longer values are cut off in the logs
+const MAX_LOGGED_VALUE_CHARS: usize = 200;
+
+/// Publishes the variables to the configured [`Output`], skipping what wouldn't change anything.
+#[derive(Debug)]
+pub struct Publisher {
+    output: Box<dyn Output>,
+    /// Re-emit every variable in `vars` once an update succeeds again after a failure
+    resync_on_error: bool,
+    /// Whether an update failed since the last resync
+    needs_resync: bool,
+    /// The last value we set for each variable, whether that update succeeded or not
+    vars: BTreeMap<String, String>,
+    /// Skip updates that wouldn't change the value a variable had after the last successful update
+    dedup: bool,
+    /// The value each variable had after the last successful update
+    written: HashMap<String, String>,
+    /// Skip all updates, e.g. while handling an event that shouldn't touch eww
+    muted: bool,
+    /// Keep the first failed update around, so the daemon can stop on it
+    strict: bool,
+    /// The variables of the first update that failed, in strict mode
+    failure: Option<Vec<String>>,
+}
+
+impl Publisher {
+    /// Publishes to the output chosen in the config. For eww, this looks up the executable.
+    pub async fn new(config: &Config) -> Result<Self, EwwError<()>> {
+        let output: Box<dyn Output> = match (&config.output_file, config.output) {
+            (Some(path), _) => Box::new(FileOutput::new(path.clone())),
+            #[cfg(feature = "dbus")]
+            (None, OutputBackend::Dbus) => Box::new(crate::dbus::DbusOutput::new(config)),
+            (None, OutputBackend::Eww) => Box::new(EwwOutput::new(config).await?),
+        };
+        Ok(Self::with_output(output, config))
+    }
+
+    /// Publishes to the given output instead of the one in the config.
+    pub fn with_output(output: Box<dyn Output>, config: &Config) -> Self {
+        Self {
+            output,
+            resync_on_error: config.resync_on_eww_error,
+            needs_resync: false,
+            vars: BTreeMap::new(),
+            dedup: !config.no_dedup,
+            written: HashMap::new(),
+            muted: false,
+            strict: config.exit_on_eww_failure,
+            failure: None,
+        }
+    }
+
+    /// Sends the given variables to the output.
+    ///
+    /// If an earlier update failed and resyncing is enabled, all known variables are sent along,
+    /// since eww might have lost them when it went down.
+    async fn update(&mut self, vars: Vec<(String, String)>) -> std::io::Result<bool> {
+        if self.muted {
+            trace!("Not updating muted variables {vars:?}");
+            return Ok(true);
+        }
+        let vars = if self.dedup {
+            let (unchanged, changed): (Vec<_>, Vec<_>) = vars
+                .into_iter()
+                .partition(|(var, val)| self.written.get(var) == Some(val));
+            for (var, _) in &unchanged {
+                trace!("Variable \"{var}\" unchanged, skipping update");
+            }
+            changed
+        } else {
+            vars
+        };
+        if vars.is_empty() {
+            return Ok(true);
+        }
+        if self.resync_on_error {
+            self.vars.extend(vars.iter().cloned());
+        }
+        let result = if self.needs_resync {
+            let all = self
+                .vars
+                .iter()
+                .map(|(var, val)| (var.clone(), val.clone()))
+                .collect::<Vec<_>>();
+            self.output.write(&all).await
+        } else {
+            self.output.write(&vars).await
+        };
+        // An IO error counts as a failed update here, the error itself goes to the caller, which usually only logs it
+        let success = matches!(result, Ok(true));
+        if self.strict && !success && self.failure.is_none() {
+            self.failure = Some(vars.iter().map(|(var, _)| var.clone()).collect());
+        }
+        for (var, value) in &vars {
+            info!(target: VARS_TARGET, var, value = truncate(value), success);
+        }
+        if success && self.dedup {
+            // A resync sent every known variable along
+            if self.needs_resync {
+                self.written.extend(self.vars.clone());
+            } else {
+                self.written.extend(vars);
+            }
+        }
+        if self.resync_on_error {
+            if !success {
+                self.needs_resync = true;
+            } else if self.needs_resync {
+                info!(
+                    "{} is reachable again, resynced {} variables",
+                    self.output.describe(),
+                    self.vars.len()
+                );
+                self.needs_resync = false;
+            }
+        }
+        result
+    }
+
+    /// Skips all updates until unmuted again.
+    pub fn set_muted(&mut self, muted: bool) {
+        self.muted = muted;
+    }
+
+    /// The first update that failed since the last call, if failures are configured to stop the daemon.
+    pub fn take_failure(&mut self) -> Option<EwwError<Infallible>> {
+        self.failure.take().map(EwwError::UpdateFailed)
+    }
+
+    /// Whether updates are currently skipped.
+    pub fn is_muted(&self) -> bool {
+        self.muted
+    }
+
+    /// Whether the output can take variables yet, e.g. whether the eww daemon is up.
+    pub async fn is_ready(&self) -> bool {
+        self.output.is_ready().await
+    }
+
+    /// Where the variables go, for the logs.
+    pub fn describe(&self) -> String {
+        self.output.describe()
+    }
+
+    pub async fn set_var<T: FromStr + ToString>(
+        &mut self,
+        var: &str,
+        val: &T,
+    ) -> Result<bool, EwwError<<T as FromStr>::Err>> {
+        let val = val.to_string();
+        let success = self
+            .update(vec![(var.to_owned(), val.clone())])
+            .await
+            .map_err(EwwError::Io)?;
+        if !success {
+            warn!("Error updating variable \"{var}\"")
+        }
+        Ok(success)
+    }
+
+    /// Updates several variables at once, e.g. with a single eww invocation.
+    pub async fn set_vars<K: AsRef<str>>(
+        &mut self,
+        vars: impl IntoIterator<Item = (K, String)>,
+    ) -> Result<bool, EwwError<Infallible>> {
+        let vars = vars
+            .into_iter()
+            .map(|(var, val)| (var.as_ref().to_owned(), val))
+            .collect::<Vec<_>>();
+        let success = self.update(vars.clone()).await.map_err(EwwError::Io)?;
+        if !success {
+            warn!("Error updating variables {vars:?}")
+        }
+        Ok(success)
+    }
+}
+
+/// Cuts off long values, like the JSON of the whole workspace list, for logging.
+fn truncate(value: &str) -> String {
+    match value.char_indices().nth(MAX_LOGGED_VALUE_CHARS) {
+        Some((end, _)) => format!("{}...", &value[..end]),
+        None => value.to_owned(),
+    }
+}
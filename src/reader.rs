@@ -0,0 +1,143 @@
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, BufReader},
+    sync::mpsc::{self, error::TrySendError},
+    task::JoinHandle,
+};
+use tracing::{trace, warn};
+
+use crate::{error::ResponseDeserializeError, event::Event, message::Message, HEADER_LENGTH};
+
+/// Event types have the highest bit set, replies don't
+const EVENT_BIT: u32 = 0x8000_0000;
+
+/// Reads the frames sent by the compositor in the background and separates replies from events.
+///
+/// Sway sends events whenever they happen, including while we wait for the reply to a request.
+/// Reading both from the same place would have us take an event for a reply or the other way around.
+pub struct FrameReader {
+    replies: mpsc::Receiver<Message>,
+    events: mpsc::Receiver<Event>,
+    /// Returns the error that ended the connection. `None` once that error was handed out.
+    task: Option<JoinHandle<ResponseDeserializeError>>,
+}
+
+impl FrameReader {
+    /// Starts reading frames from `reader`.
+    ///
+    /// At most `event_buffer_size` events are buffered until they are handled.
+    /// Once the buffer is full, further events are dropped until there is room again.
+    pub fn spawn(
+        reader: impl AsyncRead + Unpin + Send + 'static,
+        event_buffer_size: usize,
+    ) -> Self {
+        // There is only ever one request waiting for its reply
+        let (reply_sender, replies) = mpsc::channel(1);
+        let (event_sender, events) = mpsc::channel(event_buffer_size);
+        let task = tokio::spawn(read_frames(
+            BufReader::new(reader),
+            reply_sender,
+            event_sender,
+        ));
+        Self {
+            replies,
+            events,
+            task: Some(task),
+        }
+    }
+
+    /// Waits for the next reply.
+    pub async fn reply(&mut self) -> Result<Message, ResponseDeserializeError> {
+        match self.replies.recv().await {
+            Some(reply) => Ok(reply),
+            None => Err(self.closed().await),
+        }
+    }
+
+    /// Waits for the next event. This is cancel safe, so it can be used in `select!`.
+    pub async fn event(&mut self) -> Result<Event, ResponseDeserializeError> {
+        match self.events.recv().await {
+            Some(event) => Ok(event),
+            None => Err(self.closed().await),
+        }
+    }
+
+    /// The error that ended the connection.
+    async fn closed(&mut self) -> ResponseDeserializeError {
+        let Some(task) = &mut self.task else {
+            return ResponseDeserializeError::Io(std::io::ErrorKind::NotConnected.into());
+        };
+        let error = match task.await {
+            Ok(error) => error,
+            Err(e) => ResponseDeserializeError::Io(std::io::Error::other(e)),
+        };
+        self.task = None;
+        error
+    }
+}
+
+impl Drop for FrameReader {
+    fn drop(&mut self) {
+        if let Some(task) = &self.task {
+            task.abort();
+        }
+    }
+}
+
+/// Reads frames until the connection fails or both receivers are gone.
+async fn read_frames(
+    mut reader: impl AsyncRead + Unpin,
+    replies: mpsc::Sender<Message>,
+    events: mpsc::Sender<Event>,
+) -> ResponseDeserializeError {
+    loop {
+        // The header tells us whether this is a reply or an event.
+        // It is put back in front of the rest of the frame to parse it.
+        let mut header = [0u8; HEADER_LENGTH];
+        if let Err(e) = reader.read_exact(&mut header).await {
+            return e.into();
+        }
+        let frame_type = u32::from_ne_bytes(header[10..14].try_into().unwrap());
+        let mut frame = (&header[..]).chain(&mut reader);
+
+        if frame_type & EVENT_BIT != 0 {
+            let event = match Event::from_read(&mut frame).await {
+                Ok(event) => event,
+                // Newer versions of sway might send events we don't know yet.
+                // The whole frame has been read, so we can just go on with the next one.
+                Err(ResponseDeserializeError::InvalidEventType(event_type)) => {
+                    warn!("Skipping event of unknown type {event_type:#x}");
+                    continue;
+                }
+                Err(e) => return e,
+            };
+            match events.try_send(event) {
+                Ok(()) => {}
+                Err(TrySendError::Full(event)) => {
+                    warn!(
+                        "Event buffer is full, dropping {:?} event",
+                        event.event_type
+                    );
+                    trace!("Dropped payload: {}", event.payload);
+                }
+                Err(TrySendError::Closed(_)) if replies.is_closed() => {
+                    return ResponseDeserializeError::Io(std::io::ErrorKind::NotConnected.into())
+                }
+                // We might still be asked for replies
+                Err(TrySendError::Closed(_)) => {}
+            }
+        } else {
+            let reply = match Message::from_read(&mut frame).await {
+                Ok(reply) => reply,
+                // Like unknown events, this can't be the reply to anything we asked for
+                Err(ResponseDeserializeError::InvalidMessageType(message_type)) => {
+                    warn!("Skipping reply of unknown type {message_type:#x}");
+                    continue;
+                }
+                Err(e) => return e,
+            };
+            if replies.send(reply).await.is_err() && events.is_closed() {
+                return ResponseDeserializeError::Io(std::io::ErrorKind::NotConnected.into());
+            }
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use std::time::Duration;
+
+use sway_update::backoff::{Backoff, Throttle};
+use tokio::time::Instant;
+
+#[test]
+fn quick_failures_are_throttled() {
+    let mut throttle = Throttle::new(Duration::from_secs(30));
+    let start = Instant::now();
+
+    let logged = (0..10)
+        .map(|i| throttle.hit_at(start + Duration::from_secs(i)))
+        .filter(Option::is_some)
+        .count();
+    assert_eq!(logged, 1);
+
+    // The next message summarizes the attempts that weren't logged
+    assert_eq!(throttle.hit_at(start + Duration::from_secs(31)), Some(9));
+    assert_eq!(throttle.hit_at(start + Duration::from_secs(32)), None);
+}
+
+#[test]
+fn first_failure_is_logged() {
+    let mut throttle = Throttle::new(Duration::from_secs(30));
+    assert_eq!(throttle.hit(), Some(0));
+}
+
+#[test]
+fn flapping_connection_backs_off() {
+    let base = Duration::from_millis(100);
+    let mut backoff = Backoff::new(base, Duration::from_secs(10), Duration::from_secs(60));
+    let start = Instant::now();
+
+    // The connection comes up and drops again every second, so the delay keeps growing
+    let delays = (0..5)
+        .map(|i| backoff.failure_at(start + Duration::from_secs(i)))
+        .collect::<Vec<_>>();
+    assert_eq!(delays[0], base);
+    assert!(
+        delays.windows(2).all(|pair| pair[0] < pair[1]),
+        "{delays:?}"
+    );
+    assert!(delays[4] > base * 8, "{delays:?}");
+
+    // After staying up for a long time, the next failure is retried quickly again
+    let delay = backoff.failure_at(start + Duration::from_secs(3600));
+    assert!(delay < base * 2, "{delay:?}");
+}
@@ -0,0 +1,432 @@
+//! Helpers shared by the integration tests.
+
+// Not every test uses every helper
+#![allow(dead_code)]
+
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+    net::Shutdown,
+    os::unix::{fs::PermissionsExt, net::UnixListener},
+    path::{Path, PathBuf},
+    process::Command,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use sway_update::{event::EventType, message::MessageType, HEADER_LENGTH, I3_MAGIC_STRING};
+
+/// Frames a payload the way sway sends it: the magic string, the payload length and type, then the payload.
+pub fn frame(payload_type: u32, payload: &str) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(HEADER_LENGTH + payload.len());
+    bytes.extend_from_slice(&I3_MAGIC_STRING);
+    bytes.extend_from_slice(&(payload.len() as u32).to_ne_bytes());
+    bytes.extend_from_slice(&payload_type.to_ne_bytes());
+    bytes.extend_from_slice(payload.as_bytes());
+    bytes
+}
+
+/// Sends SIGTERM to the process with this id.
+pub fn sigterm(pid: u32) {
+    let status = Command::new("kill")
+        .args(["-TERM", &pid.to_string()])
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+/// Reads a recorded payload from `tests/fixtures`.
+pub fn fixture(name: &str) -> String {
+    let path = format!("{}/tests/fixtures/{name}", env!("CARGO_MANIFEST_DIR"));
+    std::fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("Could not read fixture {path}: {e}"))
+        .trim_end()
+        .to_owned()
+}
+
+/// A temporary directory that is removed again when dropped.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("sway_update-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        Self(dir)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// A stand-in for eww that logs its arguments separated by tabs, one invocation per line,
+/// and fails while a `down` file exists next to it.
+pub struct FakeEww {
+    dir: TempDir,
+}
+
+impl FakeEww {
+    pub fn new(name: &str) -> Self {
+        let dir = TempDir::new(name);
+        let script = dir.path().join("eww");
+        std::fs::write(
+            &script,
+            format!(
+                "#!/bin/sh\n[ -e '{0}/down' ] && exit 1\nprintf '%s\\t' \"$@\" >> '{0}/log'\necho >> '{0}/log'\n",
+                dir.path().display()
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+        Self { dir }
+    }
+
+    pub fn dir(&self) -> &Path {
+        self.dir.path()
+    }
+
+    pub fn binary(&self) -> String {
+        self.dir().join("eww").display().to_string()
+    }
+
+    pub fn set_down(&self, down: bool) {
+        if down {
+            std::fs::write(self.dir().join("down"), "").unwrap();
+        } else {
+            std::fs::remove_file(self.dir().join("down")).unwrap();
+        }
+    }
+
+    /// The arguments of every invocation, in order.
+    pub fn invocations(&self) -> Vec<Vec<String>> {
+        std::fs::read_to_string(self.dir().join("log"))
+            .unwrap_or_default()
+            .lines()
+            .map(|line| {
+                line.trim_end_matches('\t')
+                    .split('\t')
+                    .map(str::to_owned)
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+/// A sway that answers requests with canned replies and sends a script of events once subscribed.
+pub struct MockSway {
+    /// The replies to each request type, in order. The last one is repeated.
+    replies: HashMap<u32, Vec<String>>,
+    /// The events of each connection with how long to wait before sending each of them.
+    /// Every connection but the last one is closed after its events.
+    sessions: Vec<Vec<(EventType, String, Duration)>>,
+    /// Environment variables for the daemon
+    env: Vec<(String, String)>,
+    /// Requests that are never answered
+    unanswered: Vec<u32>,
+    /// Only put eww in `PATH`, without the system directories
+    isolated_path: bool,
+    /// Stop the daemon with SIGTERM after the last event instead of a shutdown event
+    terminate: bool,
+}
+
+/// What happened during a run of the daemon against a [`MockSway`].
+pub struct Run {
+    pub status: std::process::ExitStatus,
+    pub stdout: String,
+    pub stderr: String,
+    /// The requests the daemon sent, in order
+    pub requests: Vec<(u32, String)>,
+    /// The arguments of every eww invocation, in order
+    pub eww: Vec<Vec<String>>,
+}
+
+impl Run {
+    /// How often the daemon sent a request of this type.
+    pub fn requested(&self, message_type: MessageType) -> usize {
+        self.requests
+            .iter()
+            .filter(|(request_type, _)| *request_type == message_type as u32)
+            .count()
+    }
+
+    /// How often the daemon sent a request of this type after subscribing, i.e. because of events.
+    pub fn requested_after_subscribing(&self, message_type: MessageType) -> usize {
+        self.requests
+            .iter()
+            .skip_while(|(request_type, _)| *request_type != MessageType::Subscribe as u32)
+            .filter(|(request_type, _)| *request_type == message_type as u32)
+            .count()
+    }
+
+    /// Whether eww was invoked to set `var` to `value`, possibly along with other variables.
+    pub fn set(&self, var: &str, value: &str) -> bool {
+        let assignment = format!("{var}={value}");
+        self.eww
+            .iter()
+            .any(|invocation| invocation.contains(&assignment))
+    }
+
+    /// The last value `var` was set to.
+    pub fn last_value(&self, var: &str) -> Option<&str> {
+        let prefix = format!("{var}=");
+        self.eww
+            .iter()
+            .rev()
+            .flat_map(|invocation| invocation.iter().rev())
+            .find_map(|arg| arg.strip_prefix(&prefix))
+    }
+}
+
+impl Default for MockSway {
+    fn default() -> Self {
+        let replies = [
+            (MessageType::Subscribe, r#"{"success":true}"#),
+            (MessageType::GetTree, r#"{"id":1,"type":"root","nodes":[]}"#),
+            (
+                MessageType::GetVersion,
+                r#"{"major":1,"minor":9,"patch":0,"human_readable":"1.9"}"#,
+            ),
+            (MessageType::RunCommands, r#"[{"success":true}]"#),
+        ]
+        .into_iter()
+        .map(|(message_type, reply)| (message_type as u32, vec![reply.to_owned()]))
+        .collect();
+
+        Self {
+            replies,
+            sessions: vec![Vec::new()],
+            env: Vec::new(),
+            unanswered: Vec::new(),
+            isolated_path: false,
+            terminate: false,
+        }
+    }
+}
+
+impl MockSway {
+    /// Answers requests of this type with the given payload. Other requests get an empty list.
+    pub fn reply(mut self, message_type: MessageType, payload: &str) -> Self {
+        self.replies
+            .insert(message_type as u32, vec![payload.to_owned()]);
+        self
+    }
+
+    /// Answers the next request of this type with the given payload, once the earlier replies were sent.
+    pub fn then_reply(mut self, message_type: MessageType, payload: &str) -> Self {
+        self.replies
+            .entry(message_type as u32)
+            .or_default()
+            .push(payload.to_owned());
+        self
+    }
+
+    /// Sends this event after subscribing. Events are spaced out, so the requests they cause are answered
+    /// before the next event arrives.
+    pub fn event(mut self, event_type: EventType, payload: &str) -> Self {
+        self.events()
+            .push((event_type, payload.to_owned(), Duration::from_millis(100)));
+        self
+    }
+
+    /// Sends this event right after the previous one, without waiting for the daemon to handle it.
+    pub fn burst(mut self, event_type: EventType, payload: &str) -> Self {
+        self.events()
+            .push((event_type, payload.to_owned(), Duration::ZERO));
+        self
+    }
+
+    /// Closes the connection after the events so far, like a crashing sway, and accepts the next one.
+    /// The events after this are sent on the new connection.
+    pub fn disconnect(mut self) -> Self {
+        self.sessions.push(Vec::new());
+        self
+    }
+
+    /// The events of the current connection.
+    fn events(&mut self) -> &mut Vec<(EventType, String, Duration)> {
+        self.sessions.last_mut().unwrap()
+    }
+
+    /// Never answers requests of this type, like a hung sway.
+    pub fn hang_on(mut self, message_type: MessageType) -> Self {
+        self.unanswered.push(message_type as u32);
+        self
+    }
+
+    /// Leaves everything but the fake eww out of `PATH`, like on a system without `which`.
+    pub fn isolated_path(mut self) -> Self {
+        self.isolated_path = true;
+        self
+    }
+
+    /// Sends SIGTERM to the daemon after the last event, instead of shutting sway down.
+    pub fn terminate(mut self) -> Self {
+        self.terminate = true;
+        self
+    }
+
+    /// Sets an environment variable for the daemon, e.g. to point `I3SOCK` somewhere else.
+    pub fn env(mut self, var: &str, value: &str) -> Self {
+        self.env.push((var.to_owned(), value.to_owned()));
+        self
+    }
+
+    /// Runs the daemon with the given arguments until sway shuts down after the last event.
+    pub fn run(self, name: &str, args: &[&str]) -> Run {
+        let eww = FakeEww::new(name);
+        let socket = eww.dir().join("sway.sock");
+        let listener = UnixListener::bind(&socket).unwrap();
+
+        let Self {
+            replies,
+            mut sessions,
+            env,
+            unanswered,
+            isolated_path,
+            terminate,
+        } = self;
+        // The events of the last connection, plus some time to connect and handle them
+        let last_session = sessions.last_mut().unwrap();
+        let terminate_after = terminate.then(|| {
+            last_session
+                .iter()
+                .map(|(_, _, delay)| *delay)
+                .sum::<Duration>()
+                + Duration::from_millis(500)
+        });
+        if !terminate {
+            last_session.push((
+                EventType::Shutdown,
+                r#"{"change":"exit"}"#.to_owned(),
+                Duration::from_millis(100),
+            ));
+        }
+
+        let sway = thread::spawn(move || {
+            let mut requests = Vec::new();
+            let mut answered = HashMap::<u32, usize>::new();
+            let last_session = sessions.len() - 1;
+            for (session, events) in sessions.into_iter().enumerate() {
+                let (mut stream, _) = listener.accept().unwrap();
+                let writer = Arc::new(Mutex::new(stream.try_clone().unwrap()));
+                let mut events = Some(events);
+                loop {
+                    let mut header = [0u8; HEADER_LENGTH];
+                    if stream.read_exact(&mut header).is_err() {
+                        break;
+                    }
+                    let len = u32::from_ne_bytes(header[6..10].try_into().unwrap()) as usize;
+                    let message_type = u32::from_ne_bytes(header[10..14].try_into().unwrap());
+                    let mut payload = vec![0u8; len];
+                    stream.read_exact(&mut payload).unwrap();
+                    requests.push((message_type, String::from_utf8(payload).unwrap()));
+
+                    if unanswered.contains(&message_type) {
+                        continue;
+                    }
+                    let count = answered.entry(message_type).or_default();
+                    let reply = replies.get(&message_type).map_or("[]", |replies| {
+                        replies.get(*count).unwrap_or(replies.last().unwrap())
+                    });
+                    *count += 1;
+                    let _ = writer
+                        .lock()
+                        .unwrap()
+                        .write_all(&frame(message_type, reply));
+
+                    if message_type == MessageType::Subscribe as u32 {
+                        if let Some(events) = events.take() {
+                            let writer = writer.clone();
+                            let close = session != last_session;
+                            thread::spawn(move || {
+                                for (event_type, payload, delay) in events {
+                                    thread::sleep(delay);
+                                    let _ = writer
+                                        .lock()
+                                        .unwrap()
+                                        .write_all(&frame(event_type as u32, &payload));
+                                }
+                                if close {
+                                    // Give the daemon time to handle the last event
+                                    thread::sleep(Duration::from_millis(100));
+                                    let _ = writer.lock().unwrap().shutdown(Shutdown::Both);
+                                }
+                            });
+                        }
+                    }
+                }
+            }
+            requests
+        });
+
+        let path = if isolated_path {
+            eww.dir().display().to_string()
+        } else {
+            format!(
+                "{}:{}",
+                eww.dir().display(),
+                std::env::var("PATH").unwrap_or_default()
+            )
+        };
+        let mut child = Command::new(env!("CARGO_BIN_EXE_sway_update"))
+            .args(args)
+            .env("PATH", path)
+            .env("SWAYSOCK", &socket)
+            .env_remove("I3SOCK")
+            // Don't pick up the config file of whoever runs the tests
+            .env("XDG_CONFIG_HOME", eww.dir())
+            .envs(env)
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .spawn()
+            .unwrap();
+
+        let started = Instant::now();
+        let deadline = started + Duration::from_secs(10);
+        let mut terminate_at = terminate_after.map(|after| started + after);
+        let status = loop {
+            if let Some(status) = child.try_wait().unwrap() {
+                break status;
+            }
+            if terminate_at.is_some_and(|at| Instant::now() > at) {
+                terminate_at = None;
+                sigterm(child.id());
+            }
+            if Instant::now() > deadline {
+                child.kill().unwrap();
+                panic!("The daemon did not shut down");
+            }
+            thread::sleep(Duration::from_millis(20));
+        };
+        let mut stdout = String::new();
+        child
+            .stdout
+            .take()
+            .unwrap()
+            .read_to_string(&mut stdout)
+            .unwrap();
+        let mut stderr = String::new();
+        child
+            .stderr
+            .take()
+            .unwrap()
+            .read_to_string(&mut stderr)
+            .unwrap();
+
+        Run {
+            status,
+            stdout,
+            stderr,
+            requests: sway.join().unwrap(),
+            eww: eww.invocations(),
+        }
+    }
+}
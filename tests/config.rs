@@ -0,0 +1,291 @@
+mod common;
+
+use common::TempDir;
+use sway_update::{
+    config::{find_config_file, Config, JsonFormat, SocketType, WorkspaceFormat, WorkspaceScope},
+    error::ConfigError,
+};
+
+fn parse(args: &[&str]) -> Result<Config, ConfigError> {
+    Config::from_args(args.iter().map(|arg| arg.to_string()))
+}
+
+#[test]
+fn subscribe_all() {
+    let config = parse(&["--subscribe-all"]).unwrap();
+    assert!(config.events.contains(&"workspace".to_owned()));
+    assert!(config.events.contains(&"barconfig_update".to_owned()));
+    assert_eq!(config.events.len(), 9);
+}
+
+#[test]
+fn subscribe_all_with_events() {
+    assert!(matches!(
+        parse(&["window", "--subscribe-all"]),
+        Err(ConfigError::SubscribeAllWithEvents)
+    ));
+}
+
+#[test]
+fn socket_type_detection() {
+    assert_eq!(SocketType::Auto.resolve("localhost:4567"), SocketType::Tcp);
+    assert_eq!(SocketType::Auto.resolve("10.0.0.2:4567"), SocketType::Tcp);
+    assert_eq!(
+        SocketType::Auto.resolve("/run/user/1000/sway-ipc.1000.1234.sock"),
+        SocketType::Unix
+    );
+    assert_eq!(SocketType::Auto.resolve("/tmp/odd:1234"), SocketType::Unix);
+    assert_eq!(SocketType::Auto.resolve("sway.sock"), SocketType::Unix);
+    assert_eq!(SocketType::Unix.resolve("localhost:4567"), SocketType::Unix);
+}
+
+#[test]
+fn initial_vars_override_defaults() {
+    let config = parse(&["--initial-var", "ws_info=[1]", "--initial-var=custom=a=b"]).unwrap();
+    let vars = config.initial_vars();
+    assert_eq!(vars["binding_active"], "false");
+    assert_eq!(vars["ws_info"], "[1]");
+    assert_eq!(vars["custom"], "a=b");
+}
+
+#[test]
+fn ws_scope() {
+    assert_eq!(parse(&[]).unwrap().ws_scope, WorkspaceScope::All);
+    assert_eq!(
+        parse(&["--ws-scope", "focused-output"]).unwrap().ws_scope,
+        WorkspaceScope::FocusedOutput
+    );
+    assert_eq!(
+        parse(&["--ws-scope", "output:DP-1"]).unwrap().ws_scope,
+        WorkspaceScope::Output("DP-1".to_owned())
+    );
+    assert!(matches!(
+        parse(&["--ws-scope", "output:"]),
+        Err(ConfigError::InvalidValue { .. })
+    ));
+}
+
+#[cfg(feature = "dbus")]
+#[test]
+fn dbus_output_without_output_file() {
+    use sway_update::config::OutputBackend;
+
+    assert_eq!(
+        parse(&["--output", "dbus"]).unwrap().output,
+        OutputBackend::Dbus
+    );
+    assert!(matches!(
+        parse(&["--output", "dbus", "--output-file", "vars.json"]),
+        Err(ConfigError::OutputFileWithDbus)
+    ));
+}
+
+#[cfg(feature = "dbus")]
+#[test]
+fn invalid_dbus_names() {
+    assert!(matches!(
+        parse(&["--dbus-path", "org/swayupdate"]),
+        Err(ConfigError::InvalidValue { option, .. }) if option == "dbus-path"
+    ));
+    assert!(matches!(
+        parse(&["--dbus-interface", "swayupdate"]),
+        Err(ConfigError::InvalidValue { option, .. }) if option == "dbus-interface"
+    ));
+    assert!(parse(&["--dbus-path=/test/Vars", "--dbus-interface=test.Vars"]).is_ok());
+}
+
+#[cfg(not(feature = "dbus"))]
+#[test]
+fn dbus_output_needs_the_feature() {
+    assert!(matches!(
+        parse(&["--output", "dbus"]),
+        Err(ConfigError::DbusDisabled)
+    ));
+}
+
+#[test]
+fn json_format() {
+    let config = parse(&["--format", "pretty"]).unwrap();
+    assert_eq!(config.json_format, JsonFormat::Pretty);
+    assert_eq!(
+        config.json_format.to_string(&["a"]).unwrap(),
+        "[\n  \"a\"\n]"
+    );
+    assert_eq!(JsonFormat::Compact.to_string(&["a"]).unwrap(), r#"["a"]"#);
+}
+
+#[test]
+fn icons_from_file_and_inline() {
+    let dir = TempDir::new("icon-file");
+    let path = dir.path().join("icons.toml");
+    std::fs::write(
+        &path,
+        "# app ids\nfirefox = \"web\"\n\"org.gnome.Nautilus\" = \"files\"\n",
+    )
+    .unwrap();
+
+    let config = parse(&[
+        "--icon-file",
+        path.to_str().unwrap(),
+        "--icons=foot=terminal",
+        "--default-icon=window",
+    ])
+    .unwrap();
+    assert_eq!(config.icon(Some("firefox")), "web");
+    assert_eq!(config.icon(Some("org.gnome.Nautilus")), "files");
+    assert_eq!(config.icon(Some("foot")), "terminal");
+    assert_eq!(config.icon(Some("mpv")), "window");
+    assert_eq!(config.icon(None), "window");
+}
+
+#[test]
+fn invalid_icon_file() {
+    let dir = TempDir::new("invalid-icon-file");
+    let path = dir.path().join("icons.toml");
+    std::fs::write(&path, "firefox = \"web\"\nfoot = terminal\n").unwrap();
+
+    assert!(matches!(
+        parse(&["--icon-file", path.to_str().unwrap()]),
+        Err(ConfigError::InvalidFile { line: 2, .. })
+    ));
+}
+
+#[test]
+fn config_file_discovery() {
+    let dir = TempDir::new("config-discovery");
+    let xdg = dir.path().join("xdg");
+    let home = dir.path().join("home");
+    assert_eq!(find_config_file(Some(&xdg), Some(&home)), None);
+
+    let in_home = home.join(".config/sway_update/config.toml");
+    std::fs::create_dir_all(in_home.parent().unwrap()).unwrap();
+    std::fs::write(&in_home, "").unwrap();
+    assert_eq!(find_config_file(None, Some(&home)), Some(in_home.clone()));
+    // XDG_CONFIG_HOME takes precedence, even if there is no config file in it
+    assert_eq!(find_config_file(Some(&xdg), Some(&home)), None);
+
+    let in_xdg = xdg.join("sway_update/config.toml");
+    std::fs::create_dir_all(in_xdg.parent().unwrap()).unwrap();
+    std::fs::write(&in_xdg, "").unwrap();
+    assert_eq!(find_config_file(Some(&xdg), Some(&home)), Some(in_xdg));
+}
+
+#[test]
+fn config_file_options() {
+    let dir = TempDir::new("config-file");
+    let path = dir.path().join("config.toml");
+    std::fs::write(
+        &path,
+        "events = [\"window\", \"shutdown\"]\nreconnect = true\nseat-focus = false\neww-retries = 3\nws-format = \"kv\"\n",
+    )
+    .unwrap();
+
+    let config = Config::load(
+        ["--config", path.to_str().unwrap(), "--eww-retries=5"]
+            .into_iter()
+            .map(str::to_owned),
+    )
+    .unwrap();
+    assert_eq!(config.events, ["window", "shutdown"]);
+    assert!(config.reconnect);
+    assert!(!config.seat_focus);
+    // The command line overrides the file
+    assert_eq!(config.eww_retries, 5);
+    assert_eq!(config.ws_format, WorkspaceFormat::Kv);
+}
+
+#[test]
+fn config_file_comments() {
+    let dir = TempDir::new("config-file-comments");
+    let path = dir.path().join("config.toml");
+    std::fs::write(
+        &path,
+        "# The bar\nevents = [\"window\"] # and nothing else\neww-retries = 3 # after 1 try\nshutdown-command = \"notify-send '#bar'\"\n",
+    )
+    .unwrap();
+
+    let config = Config::load(
+        ["--config", path.to_str().unwrap()]
+            .into_iter()
+            .map(str::to_owned),
+    )
+    .unwrap();
+    assert_eq!(config.events, ["window"]);
+    assert_eq!(config.eww_retries, 3);
+    // A # inside a string doesn't start a comment
+    assert_eq!(
+        config.shutdown_command,
+        Some(vec!["notify-send".to_owned(), "#bar".to_owned()])
+    );
+}
+
+#[test]
+fn config_file_lists_and_events() {
+    let dir = TempDir::new("config-file-lists");
+    let path = dir.path().join("config.toml");
+    std::fs::write(
+        &path,
+        "events = [\"window\", \"shutdown\"]\nmode-ignore = [\"passthrough\", \"resize\"]\nws-names = [\"1=term\", \"2=web\"]\n",
+    )
+    .unwrap();
+    let load = |args: &[&str]| {
+        Config::load(
+            ["--config", path.to_str().unwrap()]
+                .iter()
+                .chain(args)
+                .map(|arg| arg.to_string()),
+        )
+    };
+
+    let config = load(&[]).unwrap();
+    assert_eq!(config.mode_ignore, ["passthrough", "resize"]);
+    assert_eq!(config.ws_names.len(), 2);
+    assert_eq!(config.events, ["window", "shutdown"]);
+
+    // Events from the command line replace the ones from the file
+    assert_eq!(load(&["mode"]).unwrap().events, ["mode"]);
+    assert_eq!(load(&["--subscribe-all"]).unwrap().events.len(), 9);
+}
+
+#[test]
+fn commands_with_quoted_arguments() {
+    let config = parse(&[
+        "--startup-command",
+        "notify-send 'hello world' \"a \\\"b\\\"\"",
+    ])
+    .unwrap();
+    assert_eq!(
+        config.startup_command,
+        Some(vec![
+            "notify-send".to_owned(),
+            "hello world".to_owned(),
+            "a \"b\"".to_owned()
+        ])
+    );
+    assert!(matches!(
+        parse(&["--shutdown-command", "notify-send 'unclosed"]),
+        Err(ConfigError::InvalidValue { .. })
+    ));
+
+    let dir = TempDir::new("config-file-command");
+    let path = dir.path().join("config.toml");
+    std::fs::write(
+        &path,
+        "shutdown-command = [\"notify-send\", \"sway, it's over\", \"bye\"]\n",
+    )
+    .unwrap();
+    let config = Config::load(
+        ["--config", path.to_str().unwrap()]
+            .into_iter()
+            .map(str::to_owned),
+    )
+    .unwrap();
+    assert_eq!(
+        config.shutdown_command,
+        Some(vec![
+            "notify-send".to_owned(),
+            "sway, it's over".to_owned(),
+            "bye".to_owned()
+        ])
+    );
+}
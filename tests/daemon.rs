@@ -0,0 +1,1316 @@
+mod common;
+
+use std::os::unix::fs::PermissionsExt;
+
+use common::{fixture, sigterm, MockSway, TempDir};
+use sway_update::{daemon::write_terminal_title, event::EventType, message::MessageType};
+
+#[test]
+fn window_change() {
+    let run = MockSway::default()
+        .event(EventType::Window, &fixture("window_focus.json"))
+        .event(EventType::Window, &fixture("window_title.json"))
+        .run("window-change", &["window", "shutdown"]);
+
+    assert!(run.status.success(), "{}", run.stderr);
+    assert!(run.set("active_window_change", "focus"));
+    assert_eq!(run.last_value("active_window_change"), Some("title"));
+}
+
+#[test]
+fn workspace_burst_is_coalesced() {
+    let run = MockSway::default()
+        .reply(MessageType::GetWorkspaces, &fixture("get_workspaces.json"))
+        .event(EventType::Workspace, &fixture("workspace_focus.json"))
+        .burst(EventType::Workspace, &fixture("workspace_focus.json"))
+        .burst(EventType::Workspace, &fixture("workspace_focus.json"))
+        .run("workspace-burst", &["workspace", "shutdown"]);
+
+    assert!(run.status.success(), "{}", run.stderr);
+    assert_eq!(
+        run.requested_after_subscribing(MessageType::GetWorkspaces),
+        1
+    );
+    assert_eq!(run.last_value("ws_list"), Some("1,2"));
+}
+
+#[test]
+fn ready_after_subscribing() {
+    let run = MockSway::default().run("ready", &["shutdown", "--emit-on-subscribe-success"]);
+
+    assert!(run.status.success(), "{}", run.stderr);
+    assert!(run.set("sway_update_ready", "true"));
+}
+
+#[test]
+fn not_ready_by_default() {
+    let run = MockSway::default().run("not-ready", &["shutdown"]);
+
+    assert!(run.status.success(), "{}", run.stderr);
+    assert_eq!(run.last_value("sway_update_ready"), None);
+}
+
+#[test]
+fn close_clears_active_window() {
+    let run = MockSway::default()
+        .event(EventType::Window, &fixture("window_focus.json"))
+        .event(EventType::Window, &fixture("window_close.json"))
+        .run("close-clears", &["window", "shutdown"]);
+
+    assert!(run.status.success(), "{}", run.stderr);
+    assert!(run.set("active_window", "~ - fish"));
+    assert_eq!(run.last_value("active_window"), Some(""));
+}
+
+#[test]
+fn focusing_empty_workspace_clears_active_window() {
+    let run = MockSway::default()
+        .event(EventType::Window, &fixture("window_focus.json"))
+        .event(EventType::Workspace, &fixture("workspace_focus.json"))
+        .run(
+            "empty-workspace-clears",
+            &["window", "workspace", "shutdown"],
+        );
+
+    assert!(run.status.success(), "{}", run.stderr);
+    assert_eq!(run.last_value("active_window"), Some(""));
+}
+
+#[test]
+fn background_title_change_is_ignored() {
+    let run = MockSway::default()
+        .event(EventType::Window, &fixture("window_focus.json"))
+        .event(EventType::Window, &fixture("window_title_background.json"))
+        .run("background-title", &["window", "shutdown"]);
+
+    assert!(run.status.success(), "{}", run.stderr);
+    assert!(!run.set("active_window", "background"));
+    assert_eq!(run.last_value("active_window"), Some("~ - fish"));
+}
+
+#[test]
+fn focused_title_change_updates_active_window() {
+    let run = MockSway::default()
+        .event(EventType::Window, &fixture("window_focus.json"))
+        .event(EventType::Window, &fixture("window_title.json"))
+        .run("focused-title", &["window", "shutdown"]);
+
+    assert!(run.status.success(), "{}", run.stderr);
+    assert_eq!(run.last_value("active_window"), Some("vim"));
+}
+
+#[test]
+fn marks_from_event() {
+    let run = MockSway::default()
+        .event(EventType::Window, &fixture("window_focus_marked.json"))
+        .run("marks-from-event", &["window", "shutdown"]);
+
+    assert!(run.status.success(), "{}", run.stderr);
+    assert!(run.set("active_window_marks", r#"["todo"]"#));
+    // Only the lookup of the focused window on startup
+    assert_eq!(run.requested(MessageType::GetTree), 1);
+}
+
+#[test]
+fn marks_from_tree_if_missing() {
+    let tree = r#"{"id":1,"type":"root","nodes":[{"id":12,"type":"con","marks":["todo"]}]}"#;
+    let run = MockSway::default()
+        .reply(MessageType::GetTree, tree)
+        .event(EventType::Window, &fixture("window_focus_no_marks.json"))
+        .run("marks-from-tree", &["window", "shutdown"]);
+
+    assert!(run.status.success(), "{}", run.stderr);
+    assert!(run.set("active_window_marks", r#"["todo"]"#));
+}
+
+#[test]
+fn once_publishes_workspaces_without_subscribing() {
+    let run = MockSway::default()
+        .reply(MessageType::GetWorkspaces, &fixture("get_workspaces.json"))
+        .run("once", &["--once"]);
+
+    assert!(run.status.success(), "{}", run.stderr);
+    assert_eq!(run.requested(MessageType::Subscribe), 0);
+    assert_eq!(run.requested(MessageType::GetWorkspaces), 1);
+    assert!(run.last_value("ws_info").is_some());
+}
+
+#[test]
+fn focused_output_from_workspaces_on_i3() {
+    // i3 doesn't report which output is focused, nor the scale and the mode
+    let outputs = r#"[
+        {"name":"xroot-0","active":false,"primary":false,"current_workspace":null,"rect":{"x":0,"y":0,"width":3840,"height":1080}},
+        {"name":"HDMI-1","active":true,"primary":false,"current_workspace":"3","rect":{"x":1920,"y":0,"width":1920,"height":1080}},
+        {"name":"DP-1","active":true,"primary":true,"current_workspace":"2","rect":{"x":0,"y":0,"width":1920,"height":1080}}
+    ]"#;
+    let run = MockSway::default()
+        .reply(MessageType::GetWorkspaces, &fixture("get_workspaces.json"))
+        .reply(MessageType::GetOutputs, outputs)
+        .run("i3-outputs", &["--once", "--compositor", "i3"]);
+
+    assert!(run.status.success(), "{}", run.stderr);
+    assert_eq!(run.last_value("focused_output"), Some("DP-1"));
+    assert_eq!(run.last_value("focus_on_primary"), Some("true"));
+}
+
+#[test]
+fn focused_output_as_reported_by_sway() {
+    let outputs = r#"[
+        {"name":"HDMI-1","active":true,"focused":true,"scale":2.0,"current_workspace":"3","current_mode":{"width":1920,"height":1080,"refresh":59940},"rect":{"x":1920,"y":0,"width":1920,"height":1080}},
+        {"name":"DP-1","active":true,"focused":false,"scale":1.0,"current_workspace":"2","current_mode":{"width":1920,"height":1080,"refresh":60000},"rect":{"x":0,"y":0,"width":1920,"height":1080}}
+    ]"#;
+    let run = MockSway::default()
+        .reply(MessageType::GetWorkspaces, &fixture("get_workspaces.json"))
+        .reply(MessageType::GetOutputs, outputs)
+        .run("sway-outputs", &["--once"]);
+
+    assert!(run.status.success(), "{}", run.stderr);
+    // Sway marks the focused output itself
+    assert_eq!(run.last_value("focused_output"), Some("HDMI-1"));
+    assert_eq!(run.last_value("focused_output_scale"), Some("2"));
+    assert_eq!(run.last_value("focused_output_refresh"), Some("59.94"));
+}
+
+#[test]
+fn query_prints_reply() {
+    let workspaces = fixture("get_workspaces.json");
+    let run = MockSway::default()
+        .reply(MessageType::GetWorkspaces, &workspaces)
+        .run("query", &["--query", "get_workspaces"]);
+
+    assert!(run.status.success(), "{}", run.stderr);
+    assert_eq!(run.stdout.trim_end(), workspaces);
+    assert_eq!(run.requests.len(), 1);
+    assert!(run.eww.is_empty());
+}
+
+#[test]
+fn print_socket_lists_candidates() {
+    // Neither eww nor sway is needed for this
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_sway_update"))
+        .arg("--print-socket")
+        .env("PATH", "")
+        .env("XDG_CONFIG_HOME", "/nonexistent")
+        .env("I3SOCK", "/run/user/1000/sway-ipc.sock")
+        .env("SWAYSOCK", "/run/user/1000/sway-ipc.sock")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "/run/user/1000/sway-ipc.sock\tI3SOCK\n"
+    );
+}
+
+#[test]
+fn active_window_from_tree_on_startup() {
+    let tree = r#"{"id":1,"type":"root","nodes":[{"id":3,"type":"workspace","nodes":[
+        {"id":12,"type":"con","name":"vim","app_id":"foot","focused":true}]}]}"#;
+    let run = MockSway::default()
+        .reply(MessageType::GetTree, tree)
+        .run("startup-focus", &["window", "shutdown"]);
+
+    assert!(run.status.success(), "{}", run.stderr);
+    assert!(run.set("active_window", "vim"));
+    assert!(run.set("active_app_id", "foot"));
+}
+
+#[test]
+fn initial_vars_before_connecting() {
+    let run = MockSway::default().run("initial-vars", &["window", "shutdown"]);
+
+    assert!(run.status.success(), "{}", run.stderr);
+    let first = &run.eww[0];
+    assert!(first.contains(&"binding_active=false".to_owned()));
+    assert!(first.contains(&"ws_info=[]".to_owned()));
+}
+
+#[test]
+fn polling_alongside_events() {
+    let run = MockSway::default()
+        .reply(MessageType::GetWorkspaces, &fixture("get_workspaces.json"))
+        .run("poll", &["--poll-interval-ms", "20", "shutdown"]);
+
+    assert!(run.status.success(), "{}", run.stderr);
+    assert_eq!(run.requested(MessageType::Subscribe), 1);
+    assert!(run.requested(MessageType::GetWorkspaces) >= 2);
+}
+
+#[test]
+fn previous_workspace() {
+    let run = MockSway::default()
+        .event(EventType::Workspace, &fixture("workspace_focus.json"))
+        .run("prev-workspace", &["workspace", "shutdown"]);
+
+    assert!(run.status.success(), "{}", run.stderr);
+    assert_eq!(run.last_value("prev_workspace"), Some("1"));
+}
+
+#[test]
+fn no_update_events_leave_eww_alone() {
+    let run = MockSway::default()
+        .event(EventType::Window, &fixture("window_focus.json"))
+        .run(
+            "no-update",
+            &["--no-update", "window", "window", "shutdown"],
+        );
+
+    assert!(run.status.success(), "{}", run.stderr);
+    assert!(run.last_value("active_window_change").is_none());
+    assert_eq!(run.last_value("active_window"), Some(""));
+}
+
+#[test]
+fn focused_workspace_and_count() {
+    let run = MockSway::default()
+        .reply(MessageType::GetWorkspaces, &fixture("get_workspaces.json"))
+        .event(EventType::Workspace, &fixture("workspace_focus.json"))
+        .run("workspace-count", &["workspace", "shutdown"]);
+
+    assert!(run.status.success(), "{}", run.stderr);
+    assert_eq!(run.last_value("focused_workspace"), Some("2"));
+    assert_eq!(run.last_value("workspace_count"), Some("2"));
+}
+
+#[test]
+fn urgent_workspace_refreshes_immediately() {
+    let urgent =
+        fixture("workspace_focus.json").replacen(r#""change":"focus""#, r#""change":"urgent""#, 1);
+    let run = MockSway::default()
+        .event(EventType::Workspace, &urgent)
+        .run("urgent-refresh", &["workspace", "shutdown"]);
+
+    assert!(run.status.success(), "{}", run.stderr);
+    // The workspaces are requested right away, before the debounced refresh that starts with the tree
+    let subscribed = run
+        .requests
+        .iter()
+        .position(|(request_type, _)| *request_type == MessageType::Subscribe as u32)
+        .unwrap();
+    assert_eq!(
+        run.requests[subscribed + 1].0,
+        MessageType::GetWorkspaces as u32
+    );
+    assert_eq!(
+        run.requested_after_subscribing(MessageType::GetWorkspaces),
+        2
+    );
+}
+
+#[test]
+fn flat_workspace_vars() {
+    let run = MockSway::default()
+        .reply(MessageType::GetWorkspaces, &fixture("get_workspaces.json"))
+        .event(EventType::Workspace, &fixture("workspace_focus.json"))
+        .run("flat-ws-vars", &["--flat-ws-vars", "workspace", "shutdown"]);
+
+    assert!(run.status.success(), "{}", run.stderr);
+    assert_eq!(run.last_value("ws_2_focused"), Some("true"));
+    assert_eq!(run.last_value("ws_1_active"), Some("true"));
+    assert_eq!(run.last_value("ws_1_focused"), Some("false"));
+    assert_eq!(run.last_value("ws_8_active"), Some("false"));
+    assert!(run.last_value("ws_info").is_some());
+}
+
+#[test]
+fn focus_on_primary_output() {
+    let outputs = r#"[{"name":"HDMI-A-1","active":true,"primary":false,"rect":{"x":0,"y":0,"width":1920,"height":1080}},
+        {"name":"DP-1","active":true,"primary":true,"focused":true,"rect":{"x":1920,"y":0,"width":1920,"height":1080}}]"#;
+    let run = MockSway::default()
+        .reply(MessageType::GetWorkspaces, &fixture("get_workspaces.json"))
+        .reply(MessageType::GetOutputs, outputs)
+        .event(EventType::Workspace, &fixture("workspace_focus.json"))
+        .run("focus-on-primary", &["workspace", "shutdown"]);
+
+    assert!(run.status.success(), "{}", run.stderr);
+    assert_eq!(run.last_value("focus_on_primary"), Some("true"));
+}
+
+#[test]
+fn ws_scope_filters_by_output() {
+    let workspaces = r#"[{"id":4,"num":1,"name":"1","output":"DP-1","focused":false,"visible":true},
+        {"id":5,"num":2,"name":"2","output":"HDMI-A-1","focused":true,"visible":true}]"#;
+    let run = |name, scope| {
+        MockSway::default()
+            .reply(MessageType::GetWorkspaces, workspaces)
+            .run(
+                name,
+                &["--once", "--prefer-name-over-num", "--ws-scope", scope],
+            )
+    };
+
+    let focused = run("ws-scope-focused", "focused-output");
+    assert!(focused.status.success(), "{}", focused.stderr);
+    assert_eq!(focused.last_value("ws_list"), Some("2"));
+
+    let named = run("ws-scope-named", "output:DP-1");
+    assert!(named.status.success(), "{}", named.stderr);
+    assert_eq!(named.last_value("ws_list"), Some("1"));
+    // The focused workspace is out of scope
+    assert_eq!(named.last_value("focused_workspace"), Some(""));
+}
+
+#[test]
+fn ws_scope_keeps_the_last_focused_output() {
+    let focused = r#"[{"id":4,"num":1,"name":"1","output":"DP-1","focused":false,"visible":true},
+        {"id":5,"num":2,"name":"2","output":"HDMI-A-1","focused":true,"visible":true}]"#;
+    // E.g. while a layer shell surface has the focus
+    let unfocused = r#"[{"id":4,"num":1,"name":"1","output":"DP-1","focused":false,"visible":true},
+        {"id":5,"num":2,"name":"2","output":"HDMI-A-1","focused":false,"visible":true},
+        {"id":6,"num":3,"name":"3","output":"HDMI-A-1","focused":false,"visible":false}]"#;
+    let run = MockSway::default()
+        .reply(MessageType::GetWorkspaces, focused)
+        .then_reply(MessageType::GetWorkspaces, unfocused)
+        .event(EventType::Workspace, &fixture("workspace_focus.json"))
+        .run(
+            "ws-scope-unfocused",
+            &[
+                "--prefer-name-over-num",
+                "--ws-scope",
+                "focused-output",
+                "workspace",
+                "shutdown",
+            ],
+        );
+
+    assert!(run.status.success(), "{}", run.stderr);
+    assert_eq!(run.requested(MessageType::GetWorkspaces), 2);
+    assert_eq!(run.last_value("ws_list"), Some("2,3"));
+}
+
+#[test]
+fn bar_colors_follow_the_json_format() {
+    // The fake eww logs one invocation per line, so the pretty JSON is read from a file instead
+    let dir = TempDir::new("bar-colors-output");
+    let path = dir.path().join("vars.json");
+    let event =
+        r##"{"id":"bar-0","mode":"dock","hidden_state":"hide","colors":{"background":"#000000"}}"##;
+    let run = MockSway::default()
+        .event(EventType::BarConfigUpdate, event)
+        .run(
+            "bar-colors",
+            &[
+                "--format",
+                "pretty",
+                "--output-file",
+                path.to_str().unwrap(),
+                "barconfig_update",
+                "shutdown",
+            ],
+        );
+
+    assert!(run.status.success(), "{}", run.stderr);
+    let vars: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(vars["bar_mode"], "dock");
+    assert_eq!(vars["bar_colors"], "{\n  \"background\": \"#000000\"\n}");
+}
+
+#[test]
+fn terminal_title_escape_sequence() {
+    let mut out = Vec::new();
+    write_terminal_title(&mut out, "vim \x1b]0;evil\x07 main.rs\n").unwrap();
+    // The control characters of the title are dropped, so it can't end the sequence early
+    assert_eq!(out, b"\x1b]0;vim ]0;evil main.rs\x07");
+}
+
+#[test]
+fn terminal_title_follows_the_active_window() {
+    let dir = TempDir::new("terminal-title-tty");
+    let tty = dir.path().join("tty");
+    std::fs::write(&tty, "").unwrap();
+    let run = MockSway::default()
+        .event(EventType::Window, &fixture("window_focus.json"))
+        .run(
+            "terminal-title",
+            &[
+                "--terminal-title",
+                tty.to_str().unwrap(),
+                "window",
+                "shutdown",
+            ],
+        );
+
+    assert!(run.status.success(), "{}", run.stderr);
+    assert_eq!(std::fs::read(&tty).unwrap(), b"\x1b]0;~ - fish\x07");
+}
+
+#[test]
+fn focused_workspace_is_published_before_the_refresh() {
+    let before = r#"[{"id":4,"num":1,"name":"1","output":"DP-1","focused":true,"visible":true},
+        {"id":5,"num":2,"name":"2","output":"DP-1","focused":false,"visible":false}]"#;
+    let run = MockSway::default()
+        .reply(MessageType::GetWorkspaces, before)
+        .then_reply(MessageType::GetWorkspaces, &fixture("get_workspaces.json"))
+        .event(EventType::Workspace, &fixture("workspace_focus.json"))
+        .run("ws-focused", &["workspace", "shutdown"]);
+
+    assert!(run.status.success(), "{}", run.stderr);
+    let position = |assignment: &str| {
+        run.eww
+            .iter()
+            .rposition(|args| args.iter().any(|arg| arg == assignment))
+    };
+    let ws_focused = position("ws_focused=2").expect("ws_focused was not published");
+    let refreshed = position("focused_workspace=2").expect("the workspaces were not refreshed");
+    // The bar can move its highlight without waiting for the whole workspace list
+    assert!(ws_focused < refreshed, "{:?}", run.eww);
+    assert_eq!(run.last_value("prev_workspace"), Some("1"));
+}
+
+#[test]
+fn urgency_follows_window_and_workspace_events() {
+    let window = |urgent: bool| {
+        let mut event: serde_json::Value =
+            serde_json::from_str(&fixture("window_focus.json")).unwrap();
+        event["change"] = "urgent".into();
+        event["container"]["urgent"] = urgent.into();
+        event.to_string()
+    };
+    let mut workspace: serde_json::Value =
+        serde_json::from_str(&fixture("workspace_focus.json")).unwrap();
+    workspace["change"] = "urgent".into();
+    let urgent_workspaces = r#"[{"id":4,"num":1,"name":"1","output":"DP-1","urgent":true,"focused":false,"visible":false},
+        {"id":5,"num":2,"name":"2","output":"DP-1","focused":true,"visible":true}]"#;
+
+    let run = MockSway::default()
+        .reply(MessageType::GetWorkspaces, &fixture("get_workspaces.json"))
+        .then_reply(MessageType::GetWorkspaces, urgent_workspaces)
+        .event(EventType::Window, &window(true))
+        .event(EventType::Workspace, &workspace.to_string())
+        .event(EventType::Window, &window(false))
+        .run("urgency", &["window", "workspace", "shutdown"]);
+
+    assert!(run.status.success(), "{}", run.stderr);
+    assert!(run.set(
+        "urgency",
+        r#"{"any":true,"count":1,"workspaces":[],"windows":["~ - fish"]}"#
+    ));
+    assert!(run.set(
+        "urgency",
+        r#"{"any":true,"count":2,"workspaces":[1],"windows":["~ - fish"]}"#
+    ));
+    assert!(run.set("any_urgent", "true"));
+    // The window stopped asking for attention, the workspace still does
+    assert_eq!(
+        run.last_value("urgency"),
+        Some(r#"{"any":true,"count":1,"workspaces":[1],"windows":[]}"#)
+    );
+}
+
+#[test]
+fn window_events_refresh_the_visible_window_count() {
+    // One output showing workspace 1, with the given windows on it
+    let tree = |windows: &[usize]| {
+        let windows = windows
+            .iter()
+            .map(|id| {
+                format!(r#"{{"id":{id},"type":"con","name":"foot","app_id":"foot","pid":{id}}}"#)
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+        format!(
+            r#"{{"id":1,"type":"root","nodes":[{{"id":2,"type":"output","name":"DP-1","current_workspace":"1","nodes":[
+                {{"id":3,"type":"workspace","name":"1","num":1,"nodes":[{windows}]}}]}}]}}"#
+        )
+    };
+    let moved = {
+        let mut event: serde_json::Value =
+            serde_json::from_str(&fixture("window_focus.json")).unwrap();
+        event["change"] = "move".into();
+        event.to_string()
+    };
+
+    let run = MockSway::default()
+        .reply(MessageType::GetTree, &tree(&[10]))
+        .then_reply(MessageType::GetTree, &tree(&[10, 12]))
+        // The focused window moved to a workspace that isn't visible
+        .then_reply(MessageType::GetTree, &tree(&[12]))
+        .then_reply(MessageType::GetTree, &tree(&[]))
+        .event(EventType::Window, &fixture("window_new.json"))
+        .event(EventType::Window, &moved)
+        .event(EventType::Window, &fixture("window_close.json"))
+        .run("visible-windows", &["window", "shutdown"]);
+
+    assert!(run.status.success(), "{}", run.stderr);
+    assert_eq!(run.requested(MessageType::GetTree), 4);
+    let counts = run
+        .eww
+        .iter()
+        .flatten()
+        .filter_map(|arg| arg.strip_prefix("visible_window_count="))
+        .collect::<Vec<_>>();
+    assert_eq!(counts, ["1", "2", "1", "0"]);
+}
+
+#[test]
+fn subscription_payload_is_json() {
+    // Sway rejects names it doesn't know, but they still have to arrive intact
+    let odd = r#"we"ird\name"#;
+    let run = MockSway::default().run("subscription-json", &["window", odd, "shutdown"]);
+
+    assert!(run.status.success(), "{}", run.stderr);
+    let (_, payload) = run
+        .requests
+        .iter()
+        .find(|(request_type, _)| *request_type == MessageType::Subscribe as u32)
+        .unwrap();
+    let events: Vec<String> = serde_json::from_str(payload).unwrap();
+    assert_eq!(events, ["window", odd, "shutdown"]);
+}
+
+#[test]
+fn named_workspaces_are_ordered_by_name() {
+    let workspaces = r#"[{"id":4,"num":-1,"name":"web","output":"DP-1","focused":true,"visible":true},
+        {"id":5,"num":-1,"name":"mail","output":"DP-1","focused":false,"visible":false},
+        {"id":6,"num":3,"name":"3:code","output":"DP-1","focused":false,"visible":false}]"#;
+    let run = MockSway::default()
+        .reply(MessageType::GetWorkspaces, workspaces)
+        .run("prefer-name", &["--once", "--prefer-name-over-num"]);
+
+    assert!(run.status.success(), "{}", run.stderr);
+    let ws_info: serde_json::Value =
+        serde_json::from_str(run.last_value("ws_info").unwrap()).unwrap();
+    // Only the existing workspaces, without empty numbered slots
+    let names = ws_info
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|workspace| workspace["name"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["3:code", "mail", "web"]);
+}
+
+#[test]
+fn repeated_modes_are_coalesced() {
+    let run = |name, args: &[&str]| {
+        let run = MockSway::default()
+            .event(EventType::Mode, &fixture("mode_resize.json"))
+            .event(EventType::Mode, &fixture("mode_resize.json"))
+            .run(name, &[args, &["--no-dedup", "mode", "shutdown"]].concat());
+        assert!(run.status.success(), "{}", run.stderr);
+        run.eww
+            .iter()
+            .filter(|args| args.iter().any(|arg| arg == "binding_mode=resize"))
+            .count()
+    };
+
+    // Without deduplication, every event would otherwise update the mode again
+    assert_eq!(run("coalesce-modes", &["--coalesce-modes"]), 1);
+    assert_eq!(run("no-coalesce-modes", &[]), 2);
+}
+
+#[test]
+fn startup_command_runs_before_connecting() {
+    let dir = TempDir::new("startup-command-marker");
+    let marker = dir.path().join("started");
+    let command = format!("touch {}", marker.display());
+    let run = MockSway::default().run(
+        "startup-command",
+        &["--startup-command", &command, "window", "shutdown"],
+    );
+    assert!(run.status.success(), "{}", run.stderr);
+    assert!(marker.exists());
+
+    let ignored = MockSway::default().run(
+        "startup-command-ignored",
+        &[
+            "--startup-command",
+            "false",
+            "--ignore-startup-failure",
+            "window",
+            "shutdown",
+        ],
+    );
+    assert!(ignored.status.success(), "{}", ignored.stderr);
+    assert_eq!(ignored.requested(MessageType::Subscribe), 1);
+
+    // Without a compositor to connect to, so only the failed command can stop the daemon this early
+    let failed = std::process::Command::new(env!("CARGO_BIN_EXE_sway_update"))
+        .args(["--startup-command", "false", "--output-file"])
+        .arg(dir.path().join("vars.json"))
+        .arg("window")
+        .env("XDG_CONFIG_HOME", "/nonexistent")
+        .env("SWAYSOCK", "/nonexistent/sway-ipc.sock")
+        .env_remove("I3SOCK")
+        .output()
+        .unwrap();
+    assert!(!failed.status.success());
+    let stderr = String::from_utf8(failed.stderr).unwrap();
+    assert!(stderr.contains("Error: StartupCommand"), "{stderr}");
+}
+
+#[test]
+fn shutdown_command_only_runs_on_exit() {
+    let dir = TempDir::new("shutdown-command-marker");
+    let marker = dir.path().join("stopped");
+    let command = format!("touch {}", marker.display());
+
+    let restart = MockSway::default()
+        .event(EventType::Shutdown, r#"{"change":"restart"}"#)
+        .run(
+            "shutdown-command-restart",
+            &["--shutdown-command", &command, "shutdown"],
+        );
+    assert!(restart.status.success(), "{}", restart.stderr);
+    assert!(!marker.exists());
+
+    let exit = MockSway::default().run(
+        "shutdown-command-exit",
+        &["--shutdown-command", &command, "shutdown"],
+    );
+    assert!(exit.status.success(), "{}", exit.stderr);
+    assert!(marker.exists());
+}
+
+#[test]
+fn log_file_error_names_the_file() {
+    let failed = std::process::Command::new(env!("CARGO_BIN_EXE_sway_update"))
+        .args(["--log-file", "/nonexistent/sway_update.log", "window"])
+        .env("XDG_CONFIG_HOME", "/nonexistent")
+        .output()
+        .unwrap();
+    assert!(!failed.status.success());
+    let stderr = String::from_utf8(failed.stderr).unwrap();
+    assert!(stderr.contains("/nonexistent/sway_update.log"), "{stderr}");
+}
+
+#[test]
+fn workspace_app_ids_from_the_tree() {
+    let tree = r#"{"id":1,"type":"root","nodes":[{"id":2,"type":"output","name":"DP-1","nodes":[
+        {"id":4,"type":"workspace","name":"1","num":1,"nodes":[
+            {"id":10,"type":"con","name":"~","app_id":"foot","pid":10},
+            {"id":11,"type":"con","name":"Mozilla Firefox","app_id":"firefox","pid":11}]},
+        {"id":5,"type":"workspace","name":"2","num":2,"nodes":[]}]}]}"#;
+    let run = MockSway::default()
+        .reply(MessageType::GetTree, tree)
+        .reply(MessageType::GetWorkspaces, &fixture("get_workspaces.json"))
+        .run(
+            "workspace-app-ids",
+            &["--once", "--prefer-name-over-num", "--workspace-app-ids"],
+        );
+
+    assert!(run.status.success(), "{}", run.stderr);
+    let ws_info: serde_json::Value =
+        serde_json::from_str(run.last_value("ws_info").unwrap()).unwrap();
+    assert_eq!(
+        ws_info[0]["app_ids"],
+        serde_json::json!(["foot", "firefox"])
+    );
+    assert_eq!(ws_info[1]["app_ids"], serde_json::json!([]));
+}
+
+#[test]
+fn no_default_mode_reset_leaves_binding_active_alone() {
+    let run = MockSway::default()
+        .reply(MessageType::GetBindingState, r#"{"name":"default"}"#)
+        .event(EventType::Mode, &fixture("mode_resize.json"))
+        .event(
+            EventType::Mode,
+            r#"{"change":"default","pango_markup":false}"#,
+        )
+        .run(
+            "no-default-mode-reset",
+            &["--no-default-mode-reset", "mode", "shutdown"],
+        );
+
+    assert!(run.status.success(), "{}", run.stderr);
+    assert!(run.set("binding_mode", "resize"));
+    assert_eq!(run.last_value("binding_mode"), Some("default"));
+    let binding_active = run
+        .eww
+        .iter()
+        .filter(|args| args.iter().any(|arg| arg.starts_with("binding_active=")))
+        .count();
+    // Only the initial value, from before connecting
+    assert_eq!(binding_active, 1);
+}
+
+#[test]
+fn seat_focus_with_the_output_from_the_tree() {
+    // seat0 focuses the node with id 7
+    let tree = r#"{"id":1,"type":"root","nodes":[{"id":2,"type":"output","name":"HDMI-A-1","nodes":[
+        {"id":4,"type":"workspace","name":"1","num":1,"nodes":[
+            {"id":7,"type":"con","name":"~","app_id":"foot","pid":7}]}]}]}"#;
+    let run = MockSway::default()
+        .reply(MessageType::GetTree, tree)
+        .reply(MessageType::GetSeats, &fixture("get_seats.json"))
+        .run("seat-focus", &["--once", "--seat-focus"]);
+
+    assert!(run.status.success(), "{}", run.stderr);
+    assert_eq!(
+        run.last_value("seat_focus"),
+        Some(r#"[{"name":"seat0","focus":7,"output":"HDMI-A-1"}]"#)
+    );
+}
+
+#[test]
+fn ws_info_as_key_value_pairs() {
+    let workspaces = r#"[{"id":4,"num":1,"name":"1","output":"DP-1","focused":true,"visible":true},
+        {"id":5,"num":2,"name":"2","output":"DP-1","urgent":true,"visible":false},
+        {"id":6,"num":3,"name":"3","output":"HDMI-A-1","visible":true},
+        {"id":7,"num":4,"name":"4","output":"HDMI-A-1","visible":false}]"#;
+    let run = MockSway::default()
+        .reply(MessageType::GetWorkspaces, workspaces)
+        .run("ws-format-kv", &["--once", "--ws-format", "kv"]);
+
+    assert!(run.status.success(), "{}", run.stderr);
+    assert_eq!(
+        run.last_value("ws_info"),
+        Some("1:focused 2:urgent 3:visible 4:active 5:inactive 6:inactive 7:inactive 8:inactive")
+    );
+}
+
+#[test]
+fn mode_variables_per_output() {
+    let run = MockSway::default()
+        .event(EventType::Mode, &fixture("mode_resize.json"))
+        .run(
+            "mode-outputs",
+            &["--mode-outputs", "DP-1,HDMI-A-1", "mode", "shutdown"],
+        );
+
+    assert!(run.status.success(), "{}", run.stderr);
+    for suffix in ["", "_DP_1", "_HDMI_A_1"] {
+        assert_eq!(
+            run.last_value(&format!("binding_mode{suffix}")),
+            Some("resize")
+        );
+        assert_eq!(
+            run.last_value(&format!("binding_active{suffix}")),
+            Some("true")
+        );
+    }
+}
+
+#[test]
+fn control_socket_does_not_replace_other_files() {
+    let dir = TempDir::new("control-socket-regular-file");
+    let path = dir.path().join("notes.txt");
+    std::fs::write(&path, "keep me").unwrap();
+    let run = MockSway::default().run(
+        "control-socket-file",
+        &[
+            "--control-socket",
+            path.to_str().unwrap(),
+            "window",
+            "shutdown",
+        ],
+    );
+
+    assert!(!run.status.success(), "{}", run.stderr);
+    assert!(run.stderr.contains("not a socket"), "{}", run.stderr);
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "keep me");
+}
+
+#[test]
+fn stale_socket_is_skipped() {
+    let run = MockSway::default()
+        .env("I3SOCK", "/nonexistent/sway-ipc.sock")
+        .run("stale-socket", &["window", "shutdown"]);
+
+    assert!(run.status.success(), "{}", run.stderr);
+    assert_eq!(run.requested(MessageType::Subscribe), 1);
+}
+
+#[test]
+fn layout_of_focused_window() {
+    let tree = r#"{"id":1,"type":"root","nodes":[{"id":2,"type":"output","name":"DP-1","nodes":[
+        {"id":3,"type":"workspace","name":"1","layout":"splith","nodes":[
+            {"id":4,"type":"con","layout":"stacked","nodes":[{"id":12,"type":"con","focused":true}]}]}]}]}"#;
+    let run = MockSway::default()
+        .reply(MessageType::GetTree, tree)
+        .event(EventType::Window, &fixture("window_focus.json"))
+        .run("layout", &["--layout", "window", "shutdown"]);
+
+    assert!(run.status.success(), "{}", run.stderr);
+    assert_eq!(run.last_value("layout"), Some("stacked"));
+}
+
+#[test]
+fn reload_refreshes_workspaces_and_mode() {
+    let run = MockSway::default()
+        .reply(MessageType::GetBindingState, r#"{"name":"resize"}"#)
+        .event(
+            EventType::Workspace,
+            r#"{"change":"reload","old":null,"current":null}"#,
+        )
+        .run(
+            "reload",
+            &["--refresh-on-reload", "workspace", "mode", "shutdown"],
+        );
+
+    assert!(run.status.success(), "{}", run.stderr);
+    assert_eq!(
+        run.requested_after_subscribing(MessageType::GetBindingState),
+        1
+    );
+    assert_eq!(run.last_value("binding_mode"), Some("resize"));
+}
+
+#[test]
+fn binding_modes_on_connect_and_reload() {
+    let run = MockSway::default()
+        .reply(MessageType::GetBindingModes, r#"["default","resize"]"#)
+        .reply(MessageType::GetBindingState, r#"{"name":"default"}"#)
+        .event(
+            EventType::Workspace,
+            r#"{"change":"reload","old":null,"current":null}"#,
+        )
+        .run(
+            "binding-modes",
+            &["--refresh-on-reload", "workspace", "shutdown"],
+        );
+
+    assert!(run.status.success(), "{}", run.stderr);
+    assert_eq!(run.requested(MessageType::GetBindingModes), 2);
+    assert_eq!(
+        run.last_value("binding_modes"),
+        Some(r#"["default","resize"]"#)
+    );
+}
+
+#[test]
+fn input_devices() {
+    let inputs = r#"[{"identifier":"1:1:AT_Translated_Set_2_keyboard","name":"AT Translated Set 2 keyboard","type":"keyboard","vendor":1}]"#;
+    let run = MockSway::default()
+        .reply(MessageType::GetInputs, inputs)
+        .reply(MessageType::GetSeats, &fixture("get_seats.json"))
+        .event(
+            EventType::Input,
+            r#"{"change":"added","input":{"identifier":"1:1:AT_Translated_Set_2_keyboard"}}"#,
+        )
+        .run("inputs", &["--inputs", "input", "shutdown"]);
+
+    assert!(run.status.success(), "{}", run.stderr);
+    assert_eq!(run.requested(MessageType::GetInputs), 2);
+    assert_eq!(
+        run.last_value("inputs"),
+        Some(
+            r#"[{"identifier":"1:1:AT_Translated_Set_2_keyboard","name":"AT Translated Set 2 keyboard","type":"keyboard"}]"#
+        )
+    );
+    assert_eq!(
+        run.last_value("seats"),
+        Some(
+            r#"[{"name":"seat0","devices":["1:1:AT_Translated_Set_2_keyboard","1739:0:Synaptics_TM3075-002"]}]"#
+        )
+    );
+    // Requesting the seats for their devices doesn't publish what they focus
+    assert!(run.last_value("seat_focus").is_none());
+}
+
+#[test]
+fn new_window_workspace() {
+    let tree = r#"{"id":1,"type":"root","nodes":[{"id":2,"type":"output","name":"DP-1","nodes":[
+        {"id":3,"type":"workspace","name":"3","nodes":[{"id":12,"type":"con"}]}]}]}"#;
+    let run = MockSway::default()
+        .reply(MessageType::GetTree, tree)
+        .event(EventType::Window, &fixture("window_new.json"))
+        .run("new-window-ws", &["window", "shutdown"]);
+
+    assert!(run.status.success(), "{}", run.stderr);
+    assert_eq!(run.last_value("new_window_ws"), Some("3"));
+}
+
+#[test]
+fn window_history_skips_repeated_focus() {
+    let fish = fixture("window_focus.json");
+    let vim = fish
+        .replace("~ - fish", "vim")
+        .replace(r#""id":12"#, r#""id":13"#);
+    let run = MockSway::default()
+        .event(EventType::Window, &fish)
+        .event(EventType::Window, &fish)
+        .event(EventType::Window, &vim)
+        .run(
+            "window-history",
+            &["--history-size=2", "window", "shutdown"],
+        );
+
+    assert!(run.status.success(), "{}", run.stderr);
+    assert_eq!(
+        run.last_value("window_history"),
+        Some(r#"["vim","~ - fish"]"#)
+    );
+}
+
+#[test]
+fn window_history_is_keyed_by_container() {
+    let fish = fixture("window_focus.json");
+    let other_fish = fish.replace(r#""id":12"#, r#""id":13"#);
+    let retitled = fish.replace("~ - fish", "vim");
+    let run = MockSway::default()
+        .event(EventType::Window, &fish)
+        .event(EventType::Window, &other_fish)
+        .event(EventType::Window, &retitled)
+        .run(
+            "window-history-by-id",
+            &["--history-size=5", "window", "shutdown"],
+        );
+
+    assert!(run.status.success(), "{}", run.stderr);
+    assert_eq!(
+        run.last_value("window_history"),
+        Some(r#"["vim","~ - fish","~ - fish"]"#)
+    );
+}
+
+#[test]
+fn subscribe_times_out() {
+    let run = MockSway::default()
+        .hang_on(MessageType::Subscribe)
+        .run("subscribe-timeout", &["--request-timeout-ms=200", "window"]);
+
+    assert!(!run.status.success());
+    assert!(run.stderr.contains("Timeout(Subscribe"), "{}", run.stderr);
+}
+
+#[test]
+fn variable_updates_are_logged() {
+    let run = MockSway::default()
+        .env("RUST_LOG", "sway_update::eww::vars=info")
+        .env("NO_COLOR", "1")
+        .event(EventType::Window, &fixture("window_focus.json"))
+        .run("vars-log", &["window", "shutdown"]);
+
+    assert!(run.status.success(), "{}", run.stderr);
+    let logged = run
+        .stderr
+        .lines()
+        .find(|line| line.contains("active_window_change"))
+        .unwrap_or_else(|| panic!("{}", run.stderr));
+    assert!(logged.contains("sway_update::eww::vars"), "{logged}");
+    assert!(logged.contains(r#"value="focus""#), "{logged}");
+    assert!(logged.contains("success=true"), "{logged}");
+}
+
+#[test]
+fn frames_are_only_dumped_with_verbose_protocol() {
+    let quiet = MockSway::default()
+        .env("RUST_LOG", "trace")
+        .env("NO_COLOR", "1")
+        .run("protocol-quiet", &["window", "shutdown"]);
+    assert!(quiet.status.success(), "{}", quiet.stderr);
+    assert!(!quiet.stderr.contains("sway_update::protocol"));
+
+    let verbose = MockSway::default().env("NO_COLOR", "1").run(
+        "protocol-verbose",
+        &["--verbose-protocol", "window", "shutdown"],
+    );
+    assert!(verbose.status.success(), "{}", verbose.stderr);
+    let subscribe = verbose
+        .stderr
+        .lines()
+        .find(|line| line.contains("sway_update::protocol") && line.contains("frame_type=0x2"))
+        .unwrap_or_else(|| panic!("{}", verbose.stderr));
+    assert!(
+        subscribe.contains(r#"header="69 33 2d 69 70 63"#),
+        "{subscribe}"
+    );
+    assert!(subscribe.contains(r#"{"success":true}"#), "{subscribe}");
+}
+
+#[test]
+fn eww_is_found_without_which() {
+    let run = MockSway::default()
+        .isolated_path()
+        .event(EventType::Window, &fixture("window_focus.json"))
+        .run("no-which", &["window", "shutdown"]);
+
+    assert!(run.status.success(), "{}", run.stderr);
+    assert_eq!(run.last_value("active_window_change"), Some("focus"));
+}
+
+#[test]
+fn ignored_modes_leave_the_mode_alone() {
+    let run = MockSway::default()
+        .event(
+            EventType::Mode,
+            r#"{"change":"passthrough","pango_markup":false}"#,
+        )
+        .event(
+            EventType::Mode,
+            r#"{"change":"default","pango_markup":false}"#,
+        )
+        .event(EventType::Mode, &fixture("mode_resize.json"))
+        .run(
+            "mode-ignore",
+            &["--mode-ignore", "passthrough", "mode", "shutdown"],
+        );
+
+    assert!(run.status.success(), "{}", run.stderr);
+    assert!(!run.set("binding_mode", "passthrough"));
+    assert_eq!(run.last_value("binding_mode"), Some("resize"));
+}
+
+#[test]
+fn mode_markup_flag() {
+    let run = MockSway::default()
+        .event(
+            EventType::Mode,
+            r#"{"change":"<b>resize</b>","pango_markup":true}"#,
+        )
+        .run("mode-markup", &["mode", "shutdown"]);
+
+    assert!(run.status.success(), "{}", run.stderr);
+    assert_eq!(run.last_value("binding_mode"), Some("<b>resize</b>"));
+    assert_eq!(run.last_value("binding_mode_markup"), Some("true"));
+}
+
+#[test]
+fn state_is_queried_on_connect() {
+    let run = MockSway::default()
+        .reply(MessageType::GetWorkspaces, &fixture("get_workspaces.json"))
+        .reply(MessageType::GetBindingState, r#"{"name":"resize"}"#)
+        .run("resync", &["window", "shutdown"]);
+
+    assert!(run.status.success(), "{}", run.stderr);
+    assert_eq!(run.requested(MessageType::GetWorkspaces), 1);
+    assert_eq!(run.last_value("binding_mode"), Some("resize"));
+    assert!(run.last_value("ws_info").is_some());
+}
+
+#[test]
+fn unchanged_workspaces_are_not_sent_again() {
+    let run = MockSway::default()
+        .reply(MessageType::GetWorkspaces, &fixture("get_workspaces.json"))
+        .event(EventType::Workspace, &fixture("workspace_focus.json"))
+        .event(EventType::Workspace, &fixture("workspace_focus.json"))
+        .run("ws-info-unchanged", &["workspace", "shutdown"]);
+
+    assert!(run.status.success(), "{}", run.stderr);
+    assert_eq!(
+        run.requested_after_subscribing(MessageType::GetWorkspaces),
+        2
+    );
+    let ws_info_updates = run
+        .eww
+        .iter()
+        .filter(|invocation| invocation.iter().any(|arg| arg.starts_with("ws_info=")))
+        .count();
+    // Once with the initial values, once after connecting
+    assert_eq!(ws_info_updates, 2);
+}
+
+#[test]
+fn urgency_is_sent_even_if_ws_info_is_unchanged() {
+    let urgent = r#"[{"id":4,"num":1,"name":"1","output":"DP-1","focused":true,"visible":true,"urgent":true}]"#;
+    let calm = urgent.replace(r#""urgent":true"#, r#""urgent":false"#);
+    let run = MockSway::default()
+        .reply(MessageType::GetWorkspaces, urgent)
+        .then_reply(MessageType::GetWorkspaces, &calm)
+        .event(EventType::Workspace, &fixture("workspace_focus.json"))
+        .run(
+            "ws-kv-urgency",
+            &["--ws-format", "kv", "workspace", "shutdown"],
+        );
+
+    assert!(run.status.success(), "{}", run.stderr);
+    // A focused workspace is "1:focused" either way
+    assert!(run
+        .last_value("ws_info")
+        .is_some_and(|ws_info| ws_info.starts_with("1:focused ")));
+    assert_eq!(run.last_value("any_urgent"), Some("false"));
+}
+
+#[test]
+fn usage_without_events() {
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_sway_update"))
+        .env("PATH", "")
+        .env("XDG_CONFIG_HOME", "/nonexistent")
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("Usage: sway_update"), "{stderr}");
+    assert!(stderr.contains("workspace, mode, window"), "{stderr}");
+}
+
+#[test]
+fn list_events() {
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_sway_update"))
+        .arg("--list-events")
+        .env("PATH", "")
+        .env("XDG_CONFIG_HOME", "/nonexistent")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let events = stdout.lines().collect::<Vec<_>>();
+    assert_eq!(events.len(), EventType::ALL.len());
+    assert_eq!(events[0], "workspace");
+    assert!(events.contains(&"barconfig_update"));
+}
+
+#[test]
+fn eww_failures_are_fatal_on_request() {
+    let lenient = MockSway::default()
+        .event(EventType::Window, &fixture("window_focus.json"))
+        .run(
+            "eww-lenient",
+            &["--eww-binary", "/bin/false", "window", "shutdown"],
+        );
+    assert!(lenient.status.success(), "{}", lenient.stderr);
+
+    // Setting the initial variables already fails, before we ever connect to sway
+    let strict = std::process::Command::new(env!("CARGO_BIN_EXE_sway_update"))
+        .args([
+            "--exit-on-eww-failure",
+            "--eww-binary",
+            "/bin/false",
+            "window",
+        ])
+        .env("PATH", "")
+        .env("XDG_CONFIG_HOME", "/nonexistent")
+        .output()
+        .unwrap();
+    assert!(!strict.status.success());
+    let stderr = String::from_utf8(strict.stderr).unwrap();
+    assert!(stderr.contains("EwwFailure"), "{stderr}");
+}
+
+#[test]
+fn reconnect_resends_values_that_changed_back() {
+    let run = MockSway::default()
+        .reply(MessageType::GetBindingState, r#"{"name":"default"}"#)
+        .event(EventType::Mode, &fixture("mode_resize.json"))
+        .disconnect()
+        .run("reconnect-dedup", &["--reconnect", "mode", "shutdown"]);
+
+    assert!(run.status.success(), "{}", run.stderr);
+    assert_eq!(run.requested(MessageType::Subscribe), 2);
+    // The second connection finds the default mode again, which eww last heard as resize
+    assert_eq!(run.last_value("binding_active"), Some("false"));
+}
+
+/// An eww that only succeeds the first time, so the initial variables get through but nothing after them.
+fn eww_failing_after_first_update(dir: &TempDir) -> String {
+    let script = dir.path().join("eww");
+    std::fs::write(
+        &script,
+        format!(
+            "#!/bin/sh\n[ -e '{0}/ran' ] && exit 1\ntouch '{0}/ran'\n",
+            dir.path().display()
+        ),
+    )
+    .unwrap();
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+    script.display().to_string()
+}
+
+#[test]
+fn eww_failures_are_not_retried_by_reconnecting() {
+    let dir = TempDir::new("eww-strict-reconnect-script");
+    let eww = eww_failing_after_first_update(&dir);
+    let run = MockSway::default()
+        .event(EventType::Window, &fixture("window_focus.json"))
+        .run(
+            "eww-strict-reconnect",
+            &[
+                "--exit-on-eww-failure",
+                "--reconnect",
+                "--eww-binary",
+                &eww,
+                "window",
+                "shutdown",
+            ],
+        );
+    assert!(!run.status.success());
+    assert!(run.stderr.contains("EwwFailure"), "{}", run.stderr);
+
+    let dir = TempDir::new("eww-strict-once-script");
+    let eww = eww_failing_after_first_update(&dir);
+    let run = MockSway::default().run(
+        "eww-strict-once",
+        &[
+            "--exit-on-eww-failure",
+            "--reconnect",
+            "--once",
+            "--eww-binary",
+            &eww,
+        ],
+    );
+    assert!(!run.status.success());
+    assert!(run.stderr.contains("EwwFailure"), "{}", run.stderr);
+}
+
+#[test]
+fn pending_title_is_flushed_on_sigterm() {
+    let run = MockSway::default()
+        .event(EventType::Window, &fixture("window_title.json"))
+        .terminate()
+        .run(
+            "flush-on-sigterm",
+            &["--title-debounce-ms", "60000", "window"],
+        );
+
+    assert!(run.status.success(), "{}", run.stderr);
+    assert_eq!(run.last_value("active_window"), Some("vim"));
+}
+
+#[test]
+fn app_id_is_debounced_with_the_title() {
+    let run = MockSway::default()
+        .event(EventType::Window, &fixture("window_focus.json"))
+        .terminate()
+        .run(
+            "debounce-app-id",
+            &[
+                "--title-debounce-ms",
+                "60000",
+                "--icons=foot=terminal",
+                "window",
+            ],
+        );
+
+    assert!(run.status.success(), "{}", run.stderr);
+    let first = |assignment: &str| {
+        run.eww
+            .iter()
+            .position(|args| args.iter().any(|arg| arg == assignment))
+    };
+    // The app id and icon never show up next to the title of the previous window
+    let title = first("active_window=~ - fish").expect("active_window was not published");
+    assert_eq!(first("active_app_id=foot"), Some(title), "{:?}", run.eww);
+    assert_eq!(
+        first("active_window_icon=terminal"),
+        Some(title),
+        "{:?}",
+        run.eww
+    );
+}
+
+#[test]
+fn sigterm_while_waiting_to_reconnect() {
+    let dir = TempDir::new("sigterm-reconnect");
+    let mut child = std::process::Command::new(env!("CARGO_BIN_EXE_sway_update"))
+        .args(["--reconnect", "--eww-binary", "/bin/true", "window"])
+        .env("SWAYSOCK", dir.path().join("missing.sock"))
+        .env_remove("I3SOCK")
+        .env("PATH", "")
+        .env("XDG_CONFIG_HOME", "/nonexistent")
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .unwrap();
+
+    // Long enough to fail connecting and start waiting
+    std::thread::sleep(std::time::Duration::from_millis(300));
+    sigterm(child.id());
+
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+    let status = loop {
+        if let Some(status) = child.try_wait().unwrap() {
+            break status;
+        }
+        if std::time::Instant::now() > deadline {
+            child.kill().unwrap();
+            panic!("The daemon ignored SIGTERM while waiting to reconnect");
+        }
+        std::thread::sleep(std::time::Duration::from_millis(20));
+    };
+    assert!(status.success());
+}
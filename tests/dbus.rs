@@ -0,0 +1,168 @@
+#![cfg(feature = "dbus")]
+
+mod common;
+
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use common::{fixture, MockSway, TempDir};
+use futures_util::StreamExt;
+use sway_update::{config::Config, dbus::DbusOutput, event::EventType, publisher::Publisher};
+use tokio::net::UnixListener;
+use zbus::{connection::Builder, message::Type, Guid, MessageStream};
+
+/// A signal as the bus received it.
+#[derive(Debug, Default, PartialEq)]
+struct Signal {
+    path: String,
+    interface: String,
+    member: String,
+    args: Vec<String>,
+}
+
+/// A stand-in for the bus that accepts any client and records the signals it sends.
+struct MockBus {
+    dir: TempDir,
+    signals: Arc<Mutex<Vec<Signal>>>,
+    connections: Arc<Mutex<usize>>,
+}
+
+impl MockBus {
+    fn new(name: &str) -> Self {
+        let dir = TempDir::new(name);
+        let socket = dir.path().join("bus");
+        let signals = Arc::new(Mutex::new(Vec::new()));
+        let connections = Arc::new(Mutex::new(0));
+        let (recorded, connected) = (signals.clone(), connections.clone());
+        let (bound, is_bound) = std::sync::mpsc::channel();
+        thread::spawn(move || {
+            let runtime = tokio::runtime::Runtime::new().unwrap();
+            runtime.block_on(async move {
+                let listener = UnixListener::bind(socket).unwrap();
+                bound.send(()).unwrap();
+                loop {
+                    let (stream, _) = listener.accept().await.unwrap();
+                    *connected.lock().unwrap() += 1;
+                    tokio::spawn(serve(stream, recorded.clone()));
+                }
+            });
+        });
+        is_bound.recv().unwrap();
+        Self {
+            dir,
+            signals,
+            connections,
+        }
+    }
+
+    fn socket(&self) -> PathBuf {
+        self.dir.path().join("bus")
+    }
+
+    /// The signals received so far, once they are `done` or a few seconds have passed.
+    fn wait_for(&self, done: impl Fn(&[Signal]) -> bool) -> std::sync::MutexGuard<'_, Vec<Signal>> {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            let signals = self.signals.lock().unwrap();
+            if done(&signals) || Instant::now() > deadline {
+                return signals;
+            }
+            drop(signals);
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    fn connections(&self) -> usize {
+        *self.connections.lock().unwrap()
+    }
+}
+
+/// Answers the Hello of the client and records its signals until it disconnects.
+async fn serve(stream: tokio::net::UnixStream, signals: Arc<Mutex<Vec<Signal>>>) {
+    let connection = Builder::unix_stream(stream)
+        .server(Guid::generate())
+        .unwrap()
+        .p2p()
+        .build()
+        .await
+        .unwrap();
+    let mut messages = MessageStream::from(&connection);
+    while let Some(Ok(message)) = messages.next().await {
+        let header = message.header();
+        match header.message_type() {
+            // Hello, which is answered with the unique name of the client
+            Type::MethodCall => connection.reply(&header, &":1.1").await.unwrap(),
+            Type::Signal => signals.lock().unwrap().push(Signal {
+                path: header.path().unwrap().to_string(),
+                interface: header.interface().unwrap().to_string(),
+                member: header.member().unwrap().to_string(),
+                args: {
+                    let (var, value) = message.body().deserialize::<(String, String)>().unwrap();
+                    vec![var, value]
+                },
+            }),
+            other => panic!("unexpected message type {other:?}"),
+        }
+    }
+}
+
+#[tokio::test]
+async fn signals_over_one_connection() {
+    let bus = MockBus::new("dbus-bus");
+    let config = Config::default();
+    let mut publisher = Publisher::with_output(
+        Box::new(DbusOutput::with_socket(bus.socket(), &config)),
+        &config,
+    );
+
+    assert!(publisher.set_var("a", &1).await.unwrap());
+    assert!(publisher
+        .set_vars([("b", "two words".to_owned()), ("c", String::new())])
+        .await
+        .unwrap());
+
+    let signal = |var: &str, value: &str| Signal {
+        path: "/org/swayupdate/Vars".to_owned(),
+        interface: "org.swayupdate.Vars".to_owned(),
+        member: "VarChanged".to_owned(),
+        args: vec![var.to_owned(), value.to_owned()],
+    };
+    assert_eq!(
+        *bus.wait_for(|signals| signals.len() >= 3),
+        [signal("a", "1"), signal("b", "two words"), signal("c", "")]
+    );
+    assert_eq!(bus.connections(), 1);
+}
+
+#[test]
+fn dbus_signals_instead_of_eww() {
+    let bus = MockBus::new("dbus-session");
+    let address = format!("unix:path={}", bus.socket().display());
+
+    let run = MockSway::default()
+        .env("DBUS_SESSION_BUS_ADDRESS", &address)
+        .event(EventType::Mode, &fixture("mode_resize.json"))
+        .run(
+            "dbus",
+            &[
+                "--output",
+                "dbus",
+                "--dbus-path",
+                "/test/Vars",
+                "mode",
+                "shutdown",
+            ],
+        );
+
+    assert!(run.status.success(), "{}", run.stderr);
+    assert!(run.eww.is_empty());
+    let is_resize =
+        |signal: &Signal| signal.path == "/test/Vars" && signal.args == ["binding_mode", "resize"];
+    let signals = bus.wait_for(|signals| signals.iter().any(is_resize));
+    assert!(signals.iter().any(is_resize), "{signals:?}");
+    assert_eq!(bus.connections(), 1);
+}
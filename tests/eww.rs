@@ -0,0 +1,297 @@
+mod common;
+
+use std::{
+    os::unix::fs::PermissionsExt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use common::{FakeEww, TempDir};
+use sway_update::{
+    config::Config,
+    error::EwwError,
+    eww::EwwOutput,
+    output::{Output, OutputFuture},
+    publisher::Publisher,
+};
+
+/// The invocations of eww with their arguments joined by spaces.
+fn invocations(fake: &FakeEww) -> Vec<String> {
+    fake.invocations()
+        .iter()
+        .map(|args| args.join(" "))
+        .collect()
+}
+
+/// Publishes to the fake eww.
+fn publisher(fake: &FakeEww, config: &Config) -> Publisher {
+    Publisher::with_output(
+        Box::new(EwwOutput::with_binary(fake.binary(), config)),
+        config,
+    )
+}
+
+#[tokio::test]
+async fn resync_after_failure() {
+    let fake = FakeEww::new("resync");
+    let config = Config {
+        resync_on_eww_error: true,
+        ..Config::default()
+    };
+    let mut publisher = publisher(&fake, &config);
+
+    assert!(publisher.set_var("a", &1).await.unwrap());
+    assert!(publisher.set_vars([("b", "2".to_owned())]).await.unwrap());
+    fake.set_down(true);
+    assert!(!publisher.set_var("c", &3).await.unwrap());
+    fake.set_down(false);
+    assert!(publisher.set_var("d", &4).await.unwrap());
+    assert!(publisher.set_var("a", &5).await.unwrap());
+
+    assert_eq!(
+        invocations(&fake),
+        [
+            "update a=1",
+            "update b=2",
+            "update a=1 b=2 c=3 d=4",
+            "update a=5",
+        ]
+    );
+}
+
+/// An output that fails with an IO error while it is broken, and records the updates it was given otherwise.
+#[derive(Debug, Clone, Default)]
+struct BrokenPipe {
+    broken: Arc<AtomicBool>,
+    updates: Arc<Mutex<Vec<String>>>,
+}
+
+impl Output for BrokenPipe {
+    fn write<'a>(
+        &'a mut self,
+        vars: &'a [(String, String)],
+    ) -> OutputFuture<'a, std::io::Result<bool>> {
+        Box::pin(async move {
+            if self.broken.load(Ordering::SeqCst) {
+                return Err(std::io::ErrorKind::BrokenPipe.into());
+            }
+            let update = vars.iter().map(|(var, val)| format!("{var}={val}"));
+            self.updates
+                .lock()
+                .unwrap()
+                .push(update.collect::<Vec<_>>().join(" "));
+            Ok(true)
+        })
+    }
+
+    fn describe(&self) -> String {
+        "a broken pipe".to_owned()
+    }
+}
+
+#[tokio::test]
+async fn io_errors_count_as_failures() {
+    let output = BrokenPipe::default();
+    let config = Config {
+        resync_on_eww_error: true,
+        exit_on_eww_failure: true,
+        ..Config::default()
+    };
+    let mut publisher = Publisher::with_output(Box::new(output.clone()), &config);
+
+    assert!(publisher.set_var("a", &1).await.unwrap());
+    output.broken.store(true, Ordering::SeqCst);
+    assert!(matches!(
+        publisher.set_var("b", &2).await,
+        Err(EwwError::Io(_))
+    ));
+    assert!(
+        matches!(publisher.take_failure(), Some(EwwError::UpdateFailed(vars)) if vars == ["b"])
+    );
+    output.broken.store(false, Ordering::SeqCst);
+    assert!(publisher.set_var("c", &3).await.unwrap());
+
+    assert_eq!(*output.updates.lock().unwrap(), ["a=1", "a=1 b=2 c=3"]);
+}
+
+#[tokio::test]
+async fn no_resync_by_default() {
+    let fake = FakeEww::new("no-resync");
+    let mut publisher = publisher(&fake, &Config::default());
+
+    assert!(publisher.set_var("a", &1).await.unwrap());
+    fake.set_down(true);
+    assert!(!publisher.set_var("b", &2).await.unwrap());
+    fake.set_down(false);
+    assert!(publisher.set_var("c", &3).await.unwrap());
+
+    assert_eq!(invocations(&fake), ["update a=1", "update c=3"]);
+}
+
+#[tokio::test]
+async fn eww_binary_from_config() {
+    let fake = FakeEww::new("eww-binary");
+    let config = Config {
+        eww_binary: Some(fake.dir().join("eww")),
+        ..Config::default()
+    };
+    assert_eq!(EwwOutput::new(&config).await.unwrap().binary, fake.binary());
+}
+
+#[tokio::test]
+async fn eww_binary_must_be_executable() {
+    let dir = TempDir::new("eww-binary-not-executable");
+    let binary = dir.path().join("eww");
+    std::fs::write(&binary, "").unwrap();
+    for path in [binary, dir.path().join("missing")] {
+        let config = Config {
+            eww_binary: Some(path),
+            ..Config::default()
+        };
+        assert!(matches!(
+            EwwOutput::new(&config).await,
+            Err(EwwError::NotExecutable(_))
+        ));
+    }
+}
+
+#[tokio::test]
+async fn daemon_running() {
+    let fake = FakeEww::new("ping");
+    let eww = EwwOutput::with_binary(fake.binary(), &Config::default());
+
+    assert!(eww.is_daemon_running().await);
+    fake.set_down(true);
+    assert!(!eww.is_daemon_running().await);
+    assert_eq!(invocations(&fake), ["ping"]);
+}
+
+#[tokio::test]
+async fn eww_config_dir_is_passed_along() {
+    let fake = FakeEww::new("eww-config-dir");
+    let config = Config {
+        eww_config_dir: Some("/home/user/.config/eww/bar".into()),
+        ..Config::default()
+    };
+    let mut publisher = publisher(&fake, &config);
+
+    assert!(publisher.set_var("a", &1).await.unwrap());
+    assert!(publisher.is_ready().await);
+    assert_eq!(
+        invocations(&fake),
+        [
+            "--config /home/user/.config/eww/bar update a=1",
+            "--config /home/user/.config/eww/bar ping"
+        ]
+    );
+}
+
+#[tokio::test]
+async fn unchanged_values_are_skipped() {
+    let fake = FakeEww::new("dedup");
+    let mut publisher = publisher(&fake, &Config::default());
+
+    assert!(publisher.set_var("a", &1).await.unwrap());
+    assert!(publisher.set_var("a", &1).await.unwrap());
+    assert!(publisher
+        .set_vars([("a", "1".to_owned()), ("b", "2".to_owned())])
+        .await
+        .unwrap());
+    // Failed updates don't count as written
+    fake.set_down(true);
+    assert!(!publisher.set_var("b", &3).await.unwrap());
+    fake.set_down(false);
+    assert!(publisher.set_var("b", &3).await.unwrap());
+
+    assert_eq!(
+        invocations(&fake),
+        ["update a=1", "update b=2", "update b=3"]
+    );
+}
+
+#[tokio::test]
+async fn no_dedup_writes_every_time() {
+    let fake = FakeEww::new("no-dedup");
+    let config = Config {
+        no_dedup: true,
+        ..Config::default()
+    };
+    let mut publisher = publisher(&fake, &config);
+
+    assert!(publisher.set_var("a", &1).await.unwrap());
+    assert!(publisher.set_var("a", &1).await.unwrap());
+
+    assert_eq!(invocations(&fake), ["update a=1", "update a=1"]);
+}
+
+#[tokio::test]
+async fn failures_are_kept_when_strict() {
+    let fake = FakeEww::new("strict");
+    let config = Config {
+        exit_on_eww_failure: true,
+        ..Config::default()
+    };
+    let mut publisher = publisher(&fake, &config);
+
+    assert!(publisher.set_var("a", &1).await.unwrap());
+    assert!(publisher.take_failure().is_none());
+    fake.set_down(true);
+    assert!(!publisher.set_var("b", &2).await.unwrap());
+    assert!(!publisher.set_var("c", &3).await.unwrap());
+    fake.set_down(false);
+
+    // Only the first failure is kept
+    assert!(
+        matches!(publisher.take_failure(), Some(EwwError::UpdateFailed(vars)) if vars == ["b"])
+    );
+    assert!(publisher.take_failure().is_none());
+}
+
+#[tokio::test]
+async fn output_file_holds_every_variable() {
+    let dir = TempDir::new("output-file");
+    let path = dir.path().join("vars.json");
+    let config = Config {
+        output_file: Some(path.clone()),
+        ..Config::default()
+    };
+    let mut publisher = Publisher::new(&config).await.unwrap();
+
+    assert!(publisher.set_var("a", &1).await.unwrap());
+    assert!(publisher
+        .set_vars([("b", "x".to_owned()), ("a", "2".to_owned())])
+        .await
+        .unwrap());
+
+    let vars: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(vars, serde_json::json!({"a": "2", "b": "x"}));
+    // Nothing is left behind from replacing the file
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+}
+
+#[tokio::test]
+async fn get_var_trims_and_reports_eww_errors() {
+    let dir = TempDir::new("eww-get");
+    let binary = dir.path().join("eww");
+    std::fs::write(
+        &binary,
+        "#!/bin/sh\ncase \"$2\" in\n\
+         a) echo ' 42 ' ;;\n\
+         missing) echo 'Variable not found \"missing\"' >&2; exit 1 ;;\n\
+         *) echo 'Failed to connect to the daemon' >&2; exit 1 ;;\n\
+         esac\n",
+    )
+    .unwrap();
+    std::fs::set_permissions(&binary, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let eww = EwwOutput::with_binary(binary.display().to_string(), &Config::default());
+
+    assert_eq!(eww.get_var::<u32>("a").await.unwrap(), Some(42));
+    assert_eq!(eww.get_var::<u32>("missing").await.unwrap(), None);
+    assert!(matches!(
+        eww.get_var::<u32>("b").await,
+        Err(EwwError::Eww(message)) if message == "Failed to connect to the daemon"
+    ));
+}
@@ -0,0 +1,154 @@
+mod common;
+
+use common::{fixture, frame};
+use sway_update::{
+    error::ResponseDeserializeError,
+    event::{Event, EventType, ModeEvent, WindowEvent, WorkspaceEvent, WorkspaceEventChange},
+    message::{Message, MessageType},
+};
+
+#[tokio::test]
+async fn workspace_event() {
+    let payload = fixture("workspace_focus.json");
+    let bytes = frame(EventType::Workspace as u32, &payload);
+
+    let event = Event::from_read(&mut &bytes[..]).await.unwrap();
+    assert_eq!(event.event_type, EventType::Workspace);
+    assert_eq!(event.payload, payload);
+
+    let event: WorkspaceEvent = serde_json::from_str(&event.payload).unwrap();
+    assert_eq!(event.change, WorkspaceEventChange::Focus);
+    assert_eq!(event.current.unwrap().num, 2);
+}
+
+#[tokio::test]
+async fn window_event() {
+    let payload = fixture("window_focus.json");
+    let bytes = frame(EventType::Window as u32, &payload);
+
+    let event = Event::from_read(&mut &bytes[..]).await.unwrap();
+    assert_eq!(event.event_type, EventType::Window);
+    assert_eq!(event.payload, payload);
+
+    let event: WindowEvent = serde_json::from_str(&event.payload).unwrap();
+    assert_eq!(event.change, "focus");
+    assert_eq!(event.container.name.as_deref(), Some("~ - fish"));
+}
+
+#[tokio::test]
+async fn mode_event() {
+    let payload = fixture("mode_resize.json");
+    let bytes = frame(EventType::Mode as u32, &payload);
+
+    let event = Event::from_read(&mut &bytes[..]).await.unwrap();
+    assert_eq!(event.event_type, EventType::Mode);
+
+    let event: ModeEvent = serde_json::from_str(&event.payload).unwrap();
+    assert_eq!(event.change, "resize");
+}
+
+#[tokio::test]
+async fn consecutive_frames() {
+    let mut bytes = frame(EventType::Mode as u32, &fixture("mode_resize.json"));
+    bytes.extend(frame(
+        EventType::Window as u32,
+        &fixture("window_focus.json"),
+    ));
+    let mut reader = &bytes[..];
+
+    let first = Event::from_read(&mut reader).await.unwrap();
+    let second = Event::from_read(&mut reader).await.unwrap();
+    assert_eq!(first.event_type, EventType::Mode);
+    assert_eq!(second.event_type, EventType::Window);
+    assert!(reader.is_empty());
+}
+
+#[tokio::test]
+async fn reply() {
+    let bytes = frame(MessageType::Subscribe as u32, r#"{"success":true}"#);
+
+    let message = Message::from_read(&mut &bytes[..]).await.unwrap();
+    assert_eq!(message.message_type, MessageType::Subscribe);
+    assert_eq!(message.payload, r#"{"success":true}"#);
+}
+
+#[tokio::test]
+async fn invalid_magic_string() {
+    let mut bytes = frame(EventType::Mode as u32, &fixture("mode_resize.json"));
+    bytes[..6].copy_from_slice(b"i3-msg");
+
+    let result = Event::from_read(&mut &bytes[..]).await;
+    assert!(matches!(
+        result,
+        Err(ResponseDeserializeError::InvalidMagicString(magic)) if magic == "i3-msg"
+    ));
+}
+
+#[tokio::test]
+async fn invalid_event_type() {
+    let mut bytes = frame(0x8000_00ff, "{}");
+    bytes.extend(frame(EventType::Mode as u32, &fixture("mode_resize.json")));
+    let mut reader = &bytes[..];
+
+    let result = Event::from_read(&mut reader).await;
+    assert!(matches!(
+        result,
+        Err(ResponseDeserializeError::InvalidEventType(0x8000_00ff))
+    ));
+
+    // The unknown frame was skipped entirely
+    let event = Event::from_read(&mut reader).await.unwrap();
+    assert_eq!(event.event_type, EventType::Mode);
+}
+
+#[tokio::test]
+async fn event_type_as_reply() {
+    // Reading an event where a reply is expected reports the type as a message type and vice versa
+    let event = frame(EventType::Mode as u32, &fixture("mode_resize.json"));
+    let reply = frame(MessageType::Subscribe as u32, r#"{"success":true}"#);
+
+    assert!(matches!(
+        Message::from_read(&mut &event[..]).await,
+        Err(ResponseDeserializeError::InvalidMessageType(0x8000_0002))
+    ));
+    assert!(matches!(
+        Event::from_read(&mut &reply[..]).await,
+        Err(ResponseDeserializeError::InvalidEventType(2))
+    ));
+}
+
+#[tokio::test]
+async fn truncated_payload() {
+    let bytes = frame(EventType::Mode as u32, &fixture("mode_resize.json"));
+
+    let result = Event::from_read(&mut &bytes[..bytes.len() - 1]).await;
+    assert!(matches!(result, Err(ResponseDeserializeError::Io(_))));
+}
+
+#[tokio::test]
+async fn replies_and_events_are_framed_alike() {
+    let payload = r#"{"change":"default","pango_markup":false}"#;
+    let reply = frame(MessageType::GetBindingState as u32, payload);
+    let event = frame(EventType::Mode as u32, payload);
+
+    let message = Message::from_read(&mut &reply[..]).await.unwrap();
+    let event = Event::from_read(&mut &event[..]).await.unwrap();
+    assert_eq!(message.message_type, MessageType::GetBindingState);
+    assert_eq!(event.event_type, EventType::Mode);
+    assert_eq!(message.payload, event.payload);
+}
+
+#[tokio::test]
+async fn unknown_reply_type_is_read_completely() {
+    let mut bytes = frame(0xff, "{}");
+    bytes.extend(frame(MessageType::Subscribe as u32, r#"{"success":true}"#));
+    let mut reader = &bytes[..];
+
+    let result = Message::from_read(&mut reader).await;
+    assert!(matches!(
+        result,
+        Err(ResponseDeserializeError::InvalidMessageType(0xff))
+    ));
+    let message = Message::from_read(&mut reader).await.unwrap();
+    assert_eq!(message.message_type, MessageType::Subscribe);
+}
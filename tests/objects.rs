@@ -0,0 +1,178 @@
+mod common;
+
+use common::fixture;
+use sway_update::objects::{
+    find_focused, CommandResult, Node, Seat, SeatInputs, Window, Workspace, WorkspaceInfo,
+    WorkspaceInfoEnvelope,
+};
+
+fn window(json: &str) -> Window {
+    serde_json::from_str(json).unwrap()
+}
+
+#[test]
+fn title_is_the_window_name() {
+    let window = window(r#"{"id":1,"name":"~ - fish","app_id":"foot"}"#);
+    assert_eq!(window.title(), Some("~ - fish"));
+}
+
+#[test]
+fn blank_title_falls_back_to_app_id() {
+    let window = window(r#"{"id":1,"name":"   \t","app_id":"foot"}"#);
+    assert_eq!(window.title(), Some("foot"));
+}
+
+#[test]
+fn blank_title_falls_back_to_x11_title() {
+    let window = window(
+        r#"{"id":1,"name":" ","window_properties":{"class":"Firefox","title":"Mozilla Firefox"}}"#,
+    );
+    assert_eq!(window.title(), Some("Mozilla Firefox"));
+}
+
+#[test]
+fn blank_title_without_fallback() {
+    let window = window(r#"{"id":1,"name":"  "}"#);
+    assert_eq!(window.title(), None);
+}
+
+#[test]
+fn workspace_list_is_sorted() {
+    let workspaces = [3, 1, 5].map(|num| WorkspaceInfo::new(&num.to_string(), num));
+    assert_eq!(WorkspaceInfo::list(&workspaces), "1,3,5");
+}
+
+#[test]
+fn workspace_list_skips_named_workspaces() {
+    let workspaces = [WorkspaceInfo::new("2", 2), WorkspaceInfo::new("mail", -1)];
+    assert_eq!(WorkspaceInfo::list(&workspaces), "2");
+}
+
+#[test]
+fn shown_on_output() {
+    let workspace = |visible| {
+        let workspace: Workspace = serde_json::from_str(&format!(
+            r#"{{"id":4,"num":1,"name":"1","output":"DP-1","visible":{visible}}}"#
+        ))
+        .unwrap();
+        WorkspaceInfo::from(workspace)
+    };
+
+    assert_eq!(workspace(true).shown_on, "DP-1");
+    assert_eq!(workspace(false).shown_on, "");
+}
+
+#[test]
+fn window_marks() {
+    let marked = window(r#"{"id":1,"marks":["todo","mail"]}"#);
+    assert_eq!(
+        marked.marks,
+        Some(vec!["todo".to_owned(), "mail".to_owned()])
+    );
+    assert_eq!(window(r#"{"id":1}"#).marks, None);
+}
+
+#[test]
+fn focused_leaf() {
+    let tree: Node = serde_json::from_str(
+        r#"{"id":1,"type":"root","nodes":[{"id":2,"type":"workspace","nodes":[
+            {"id":3,"type":"con","nodes":[{"id":4,"type":"con"},{"id":5,"type":"con","focused":true}]}]}]}"#,
+    )
+    .unwrap();
+    assert_eq!(find_focused(&tree).map(|node| node.id), Some(5));
+
+    let empty_workspace: Node = serde_json::from_str(
+        r#"{"id":1,"type":"root","nodes":[{"id":2,"type":"workspace","focused":true}]}"#,
+    )
+    .unwrap();
+    assert!(find_focused(&empty_workspace).is_none());
+}
+
+#[test]
+fn layout_of_focused_container() {
+    let tree: Node = serde_json::from_str(
+        r#"{"id":1,"type":"root","nodes":[
+            {"id":2,"type":"workspace","layout":"splith","focus":[3],
+                "nodes":[{"id":3,"type":"con","layout":"tabbed","focus":[5],"nodes":[{"id":4,"type":"con"},{"id":5,"type":"con"}]}],
+                "floating_nodes":[{"id":6,"type":"floating_con"}]},
+            {"id":7,"type":"workspace","layout":"splitv"}]}"#,
+    )
+    .unwrap();
+    assert_eq!(tree.layout_of(2), Some("tabbed"));
+    assert_eq!(tree.layout_of(4), Some("tabbed"));
+    assert_eq!(tree.layout_of(3), Some("tabbed"));
+    assert_eq!(tree.layout_of(6), Some("floating"));
+    assert_eq!(tree.layout_of(7), Some("splitv"));
+    assert_eq!(tree.layout_of(8), None);
+}
+
+#[test]
+fn scratchpad_count() {
+    let tree: Node = serde_json::from_str(
+        r#"{"id":1,"type":"root","nodes":[
+            {"id":2,"type":"output","name":"__i3","nodes":[{"id":3,"type":"workspace","name":"__i3_scratch",
+                "floating_nodes":[{"id":4,"type":"floating_con"},{"id":5,"type":"floating_con"}]}]},
+            {"id":6,"type":"output","name":"DP-1","nodes":[{"id":7,"type":"workspace","name":"1",
+                "floating_nodes":[{"id":8,"type":"floating_con"}]}]}]}"#,
+    )
+    .unwrap();
+    assert_eq!(tree.scratchpad_count(), 2);
+
+    let no_scratchpad: Node = serde_json::from_str(r#"{"id":1,"type":"root"}"#).unwrap();
+    assert_eq!(no_scratchpad.scratchpad_count(), 0);
+}
+
+#[test]
+fn visible_window_count_excludes_the_scratchpad() {
+    // Sway reports the scratchpad workspace as visible, its windows still don't count
+    let tree: Node = serde_json::from_str(
+        r#"{"id":1,"type":"root","nodes":[
+            {"id":2,"type":"output","name":"__i3","nodes":[{"id":3,"type":"workspace","name":"__i3_scratch","visible":true,
+                "floating_nodes":[{"id":4,"type":"floating_con"},{"id":5,"type":"floating_con"}]}]},
+            {"id":6,"type":"output","name":"DP-1","current_workspace":"1","nodes":[
+                {"id":7,"type":"workspace","name":"1","visible":true,
+                    "nodes":[{"id":8,"type":"con"}],"floating_nodes":[{"id":9,"type":"floating_con"}]},
+                {"id":10,"type":"workspace","name":"2","visible":false,"nodes":[{"id":11,"type":"con"}]}]}]}"#,
+    )
+    .unwrap();
+    assert_eq!(tree.scratchpad_count(), 2);
+    assert_eq!(tree.visible_window_count(), 2);
+}
+
+#[test]
+fn workspace_envelope() {
+    let workspaces = [WorkspaceInfo::new("1", 1)];
+    let json = serde_json::to_value(WorkspaceInfoEnvelope::new(&workspaces)).unwrap();
+    assert_eq!(json["version"], 1);
+    assert_eq!(json["workspaces"][0]["name"], "1");
+}
+
+#[test]
+fn command_parse_error() {
+    let results: Vec<CommandResult> = serde_json::from_str(
+        r#"[{"success":true},{"success":false,"parse_error":true,"error":"Unknown command"}]"#,
+    )
+    .unwrap();
+    assert!(!results[0].parse_error);
+    assert!(results[1].parse_error);
+    assert_eq!(results[1].error.as_deref(), Some("Unknown command"));
+}
+
+#[test]
+fn seat_devices() {
+    let seats: Vec<Seat> = serde_json::from_str(&fixture("get_seats.json")).unwrap();
+    let devices = &seats[0].devices;
+    assert_eq!(devices.len(), 2);
+    assert_eq!(devices[1].identifier, "1739:0:Synaptics_TM3075-002");
+    assert_eq!(devices[1].input_type, "touchpad");
+
+    let summary = SeatInputs::from(seats[0].clone());
+    assert_eq!(summary.name, "seat0");
+    assert_eq!(
+        summary.devices,
+        [
+            "1:1:AT_Translated_Set_2_keyboard",
+            "1739:0:Synaptics_TM3075-002"
+        ]
+    );
+}
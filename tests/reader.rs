@@ -0,0 +1,105 @@
+mod common;
+
+use common::{fixture, frame};
+use sway_update::{
+    error::ResponseDeserializeError, event::EventType, message::MessageType, reader::FrameReader,
+};
+use tokio::io::AsyncWriteExt;
+
+#[tokio::test]
+async fn events_and_replies_are_separated() {
+    let (mut sway, daemon) = tokio::io::duplex(4096);
+    let mut frames = FrameReader::spawn(daemon, 16);
+
+    sway.write_all(&frame(EventType::Mode as u32, &fixture("mode_resize.json")))
+        .await
+        .unwrap();
+    sway.write_all(&frame(MessageType::GetMarks as u32, "[]"))
+        .await
+        .unwrap();
+
+    // The event sent first doesn't get in the way of the reply
+    let reply = frames.reply().await.unwrap();
+    assert_eq!(reply.message_type, MessageType::GetMarks);
+    let event = frames.event().await.unwrap();
+    assert_eq!(event.event_type, EventType::Mode);
+}
+
+#[tokio::test]
+async fn unknown_frames_are_skipped() {
+    let (mut sway, daemon) = tokio::io::duplex(4096);
+    let mut frames = FrameReader::spawn(daemon, 16);
+
+    sway.write_all(&frame(0x8000_00ff, "{}")).await.unwrap();
+    sway.write_all(&frame(0xff, "{}")).await.unwrap();
+    sway.write_all(&frame(EventType::Mode as u32, &fixture("mode_resize.json")))
+        .await
+        .unwrap();
+    sway.write_all(&frame(MessageType::GetMarks as u32, "[]"))
+        .await
+        .unwrap();
+
+    // The connection is still read after frames of a type we don't know
+    let reply = frames.reply().await.unwrap();
+    assert_eq!(reply.message_type, MessageType::GetMarks);
+    let event = frames.event().await.unwrap();
+    assert_eq!(event.event_type, EventType::Mode);
+}
+
+#[tokio::test]
+async fn full_event_buffer_drops_events() {
+    let (mut sway, daemon) = tokio::io::duplex(4096);
+    let mut frames = FrameReader::spawn(daemon, 2);
+
+    for change in ["a", "b", "c", "d", "e"] {
+        let payload = format!(r#"{{"change":"{change}","pango_markup":false}}"#);
+        sway.write_all(&frame(EventType::Mode as u32, &payload))
+            .await
+            .unwrap();
+    }
+    sway.write_all(&frame(MessageType::GetMarks as u32, "[]"))
+        .await
+        .unwrap();
+    drop(sway);
+
+    // Once the reply arrived, all events before it have been read
+    frames.reply().await.unwrap();
+
+    // Only the events that fit into the buffer are kept
+    assert!(frames.event().await.unwrap().payload.contains(r#""a""#));
+    assert!(frames.event().await.unwrap().payload.contains(r#""b""#));
+    assert!(matches!(
+        frames.event().await,
+        Err(ResponseDeserializeError::Io(_))
+    ));
+}
+
+#[tokio::test]
+async fn interleaved_frames_survive_partial_reads() {
+    const FRAMES: usize = 500;
+    // A tiny pipe makes frames arrive in pieces that straddle frame boundaries
+    let (mut sway, daemon) = tokio::io::duplex(7);
+    let mut frames = FrameReader::spawn(daemon, FRAMES);
+
+    let writer = tokio::spawn(async move {
+        for i in 0..FRAMES {
+            let payload = format!(r#"{{"change":"{i}","pango_markup":false}}"#);
+            sway.write_all(&frame(EventType::Mode as u32, &payload))
+                .await
+                .unwrap();
+            sway.write_all(&frame(MessageType::GetMarks as u32, &format!("[\"{i}\"]")))
+                .await
+                .unwrap();
+        }
+    });
+
+    for i in 0..FRAMES {
+        let reply = frames.reply().await.unwrap();
+        assert_eq!(reply.payload, format!("[\"{i}\"]"));
+    }
+    for i in 0..FRAMES {
+        let event = frames.event().await.unwrap();
+        assert!(event.payload.contains(&format!(r#""{i}""#)));
+    }
+    writer.await.unwrap();
+}
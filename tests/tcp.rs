@@ -0,0 +1,38 @@
+mod common;
+
+use common::{frame, FakeEww};
+use sway_update::{
+    config::Config, daemon::Daemon, eww::EwwOutput, message::MessageType, publisher::Publisher,
+    HEADER_LENGTH,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+};
+
+#[tokio::test]
+async fn connect_over_tcp() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let sway = tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut header = [0u8; HEADER_LENGTH];
+        stream.read_exact(&mut header).await.unwrap();
+        let message_type = u32::from_ne_bytes(header[10..14].try_into().unwrap());
+        let reply = r#"{"major":1,"minor":9,"patch":0,"human_readable":"1.9"}"#;
+        stream.write_all(&frame(message_type, reply)).await.unwrap();
+        message_type
+    });
+
+    let fake = FakeEww::new("tcp");
+    let config = Config::default();
+    let mut publisher = Publisher::with_output(
+        Box::new(EwwOutput::with_binary(fake.binary(), &config)),
+        &config,
+    );
+    let mut daemon = Daemon::new(&address, &mut publisher, config).await.unwrap();
+    daemon.get_version().await.unwrap();
+
+    assert_eq!(sway.await.unwrap(), MessageType::GetVersion as u32);
+    assert_eq!(fake.invocations(), [["update", "sway_version=1.9"]]);
+}